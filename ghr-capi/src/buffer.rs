@@ -25,7 +25,8 @@ use super::buffer_type::BufferType;
 
 #[no_mangle]
 pub unsafe extern "C" fn GHR_FreeBuffer(handle: *mut c_void) {
-    Box::from_raw(handle);
+    // The handle is a boxed pointer to the buffer, whose layout does not depend on the type of the buffer
+    drop(Box::from_raw(handle as *mut *mut dyn AmplitudeFieldBuffer));
 }
 
 #[no_mangle]
@@ -44,7 +45,7 @@ pub unsafe extern "C" fn GHR_GetScalarBufferArray(
             *out = ptr as *const c_void;
             len as u64
         }};
-    };
+    }
     match BufferType::from_i32(buffer_type) {
         BufferType::AmplitudeFieldBuffer => get_scalar!(AmplitudeFieldBuffer),
         BufferType::IntensityFieldBuffer => get_scalar!(IntensityFieldBuffer),
@@ -60,7 +61,7 @@ pub unsafe extern "C" fn GHR_GetScalarMax(handle: *mut c_void, buffer_type: i32)
             forget(buffer);
             max
         }};
-    };
+    }
     match BufferType::from_i32(buffer_type) {
         BufferType::AmplitudeFieldBuffer => get_max!(AmplitudeFieldBuffer),
         BufferType::IntensityFieldBuffer => get_max!(IntensityFieldBuffer),
@@ -82,7 +83,7 @@ pub unsafe extern "C" fn GHR_GetBounds(
             forget(buffer);
             bounds
         }};
-    };
+    }
     let bounds = match BufferType::from_i32(buffer_type) {
         BufferType::AmplitudeFieldBuffer => get_bounds!(AmplitudeFieldBuffer),
         BufferType::IntensityFieldBuffer => get_bounds!(IntensityFieldBuffer),
@@ -107,7 +108,7 @@ pub unsafe extern "C" fn GHR_GetDimension(
             forget(buffer);
             dimension
         }};
-    };
+    }
     let dimension = match BufferType::from_i32(buffer_type) {
         BufferType::AmplitudeFieldBuffer => get_dimension!(AmplitudeFieldBuffer),
         BufferType::IntensityFieldBuffer => get_dimension!(IntensityFieldBuffer),
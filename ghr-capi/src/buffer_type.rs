@@ -11,10 +11,7 @@
  *
  */
 
-use num_derive::FromPrimitive;
-use num_traits::FromPrimitive;
-
-#[derive(FromPrimitive, Copy, Clone)]
+#[derive(Copy, Clone)]
 #[repr(i32)]
 pub enum BufferType {
     AmplitudeFieldBuffer = 1,
@@ -23,6 +20,10 @@ pub enum BufferType {
 
 impl BufferType {
     pub fn from_i32(x: i32) -> Self {
-        FromPrimitive::from_i32(x).unwrap()
+        match x {
+            1 => BufferType::AmplitudeFieldBuffer,
+            2 => BufferType::IntensityFieldBuffer,
+            _ => panic!("unknown buffer type: {}", x),
+        }
     }
 }
@@ -30,7 +30,7 @@ pub unsafe extern "C" fn GHR_CreateBufferBuilder(out: *mut *mut BufferBuilder) {
 
 #[no_mangle]
 pub unsafe extern "C" fn GHR_FreeBufferBuilder(handle: *mut BufferBuilder) {
-    Box::from_raw(handle);
+    drop(Box::from_raw(handle));
 }
 
 #[no_mangle]
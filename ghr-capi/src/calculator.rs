@@ -11,9 +11,13 @@
  *
  */
 
-use ghr::{calculator::*, wave_source::WaveSource};
+use ghr::{calculator::*, calibration::Calibration, wave_source::WaveSource, Float};
 
-use std::{ffi::c_void, mem::forget};
+use std::{
+    ffi::{c_void, CStr},
+    mem::forget,
+    os::raw::c_char,
+};
 
 #[no_mangle]
 pub unsafe extern "C" fn GHR_CreateCpuCalculator(out: *mut *mut c_void) {
@@ -52,3 +56,49 @@ pub unsafe extern "C" fn GHR_WaveSources(handle: *mut c_void, out: *mut *mut c_v
     *out = ptr as *mut c_void;
     len as u64
 }
+
+/// Set the phase offset and the gain of each wave source. The calibration is removed if both lengths are 0.
+/// Returns false if the lengths are different.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_SetCalibration(
+    handle: *mut c_void,
+    phase_offsets: *const Float,
+    phase_offsets_len: u64,
+    gains: *const Float,
+    gains_len: u64,
+) -> bool {
+    if phase_offsets_len != gains_len {
+        return false;
+    }
+    let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
+    let calibration = if gains_len == 0 {
+        None
+    } else {
+        let len = gains_len as usize;
+        Some(Calibration::new(
+            std::slice::from_raw_parts(phase_offsets, len),
+            std::slice::from_raw_parts(gains, len),
+        ))
+    };
+    (*calc).set_calibration(calibration);
+    forget(calc);
+    true
+}
+
+/// Load the calibration from a CSV file at `path`. Returns false if it cannot be loaded.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_LoadCalibration(handle: *mut c_void, path: *const c_char) -> bool {
+    let calibration = match CStr::from_ptr(path).to_str() {
+        Ok(path) => Calibration::from_csv(path),
+        Err(_) => return false,
+    };
+    match calibration {
+        Ok(calibration) => {
+            let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
+            (*calc).set_calibration(Some(calibration));
+            forget(calc);
+            true
+        }
+        Err(_) => false,
+    }
+}
@@ -11,9 +11,16 @@
  *
  */
 
-use std::{ffi::c_void, mem::forget};
+use std::{
+    cell::RefCell,
+    ffi::{c_void, CStr},
+    mem::forget,
+    os::raw::c_char,
+    time::Duration,
+};
 
-use ghr::{calculator::*, optimizer::*, Float, Vector3};
+use ghr::{calculator::*, optimizer::*, transfer_matrix::Propagation, Complex, Float, Vector3};
+use ndarray::Array;
 
 #[no_mangle]
 pub unsafe extern "C" fn GHR_GreedyBruteForce(
@@ -24,18 +31,54 @@ pub unsafe extern "C" fn GHR_GreedyBruteForce(
     phase_div: u64,
     amp_div: u64,
     randamize: bool,
-) {
+) -> bool {
+    GHR_GreedyBruteForceWeighted(
+        handle,
+        foci,
+        amps,
+        std::ptr::null(),
+        size,
+        phase_div,
+        amp_div,
+        randamize,
+    )
+}
+
+/// Same as `GHR_GreedyBruteForce` with the weight of each focus. `weights` may be null.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GHR_GreedyBruteForceWeighted(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    phase_div: u64,
+    amp_div: u64,
+    randamize: bool,
+) -> bool {
     let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
     let len = size as usize;
     let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
     let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
     let mut gfs = GreedyBruteForce::new(phase_div as _, amp_div as _, randamize);
     gfs.set_target_foci(foci);
     gfs.set_target_amps(amps);
-    gfs.optimize((*calc).wave_sources());
+    if let Some(weights) = weights {
+        gfs.set_target_weights(weights);
+    }
+    let res = gfs.optimize((*calc).wave_sources());
     forget(calc);
+    res.is_ok()
 }
 
+/// Horn does not take the weights of the foci into account, so there is no weighted variant.
+/// The targets with weights or fixed phases set elsewhere make it fail.
 #[no_mangle]
 pub unsafe extern "C" fn GHR_Horn(
     handle: *mut c_void,
@@ -45,7 +88,7 @@ pub unsafe extern "C" fn GHR_Horn(
     repeat: u64,
     alpha: Float,
     lambda: Float,
-) {
+) -> bool {
     let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
     let len = size as usize;
     let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
@@ -53,8 +96,9 @@ pub unsafe extern "C" fn GHR_Horn(
     let mut horn = Horn::new(repeat as _, alpha, lambda);
     horn.set_target_foci(foci);
     horn.set_target_amps(amps);
-    horn.optimize((*calc).wave_sources());
+    let res = horn.optimize((*calc).wave_sources());
     forget(calc);
+    res.is_ok()
 }
 
 #[no_mangle]
@@ -64,16 +108,38 @@ pub unsafe extern "C" fn GHR_Long(
     amps: *const Float,
     size: u64,
     gamma: Float,
-) {
+) -> bool {
+    GHR_LongWeighted(handle, foci, amps, std::ptr::null(), size, gamma)
+}
+
+/// Same as `GHR_Long` with the weight of each focus. `weights` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_LongWeighted(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    gamma: Float,
+) -> bool {
     let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
     let len = size as usize;
     let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
     let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
     let mut long = Long::new(gamma);
     long.set_target_foci(foci);
     long.set_target_amps(amps);
-    long.optimize((*calc).wave_sources());
+    if let Some(weights) = weights {
+        long.set_target_weights(weights);
+    }
+    let res = long.optimize((*calc).wave_sources());
     forget(calc);
+    res.is_ok()
 }
 
 #[no_mangle]
@@ -86,16 +152,52 @@ pub unsafe extern "C" fn GHR_LM(
     eps_2: Float,
     tau: Float,
     repeat: u64,
-) {
+) -> bool {
+    GHR_LMWeighted(
+        handle,
+        foci,
+        amps,
+        std::ptr::null(),
+        size,
+        eps_1,
+        eps_2,
+        tau,
+        repeat,
+    )
+}
+
+/// Same as `GHR_LM` with the weight of each focus. `weights` may be null.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GHR_LMWeighted(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    eps_1: Float,
+    eps_2: Float,
+    tau: Float,
+    repeat: u64,
+) -> bool {
     let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
     let len = size as usize;
     let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
     let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
     let mut lm = LM::new(eps_1, eps_2, tau, repeat as _);
     lm.set_target_foci(foci);
     lm.set_target_amps(amps);
-    lm.optimize((*calc).wave_sources());
+    if let Some(weights) = weights {
+        lm.set_target_weights(weights);
+    }
+    let res = lm.optimize((*calc).wave_sources());
     forget(calc);
+    res.is_ok()
 }
 
 #[no_mangle]
@@ -105,14 +207,388 @@ pub unsafe extern "C" fn GHR_GSPAT(
     amps: *const Float,
     size: u64,
     repeat: u64,
-) {
+) -> bool {
+    GHR_GSPATWeighted(handle, foci, amps, std::ptr::null(), size, repeat)
+}
+
+/// Same as `GHR_GSPAT` with the weight of each focus. `weights` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_GSPATWeighted(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    repeat: u64,
+) -> bool {
     let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
     let len = size as usize;
     let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
     let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
     let mut gspat = GSPAT::new(repeat as _);
     gspat.set_target_foci(foci);
     gspat.set_target_amps(amps);
-    gspat.optimize((*calc).wave_sources());
+    if let Some(weights) = weights {
+        gspat.set_target_weights(weights);
+    }
+    let res = gspat.optimize((*calc).wave_sources());
+    forget(calc);
+    res.is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn GHR_Naive(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    size: u64,
+) -> bool {
+    GHR_NaiveWeighted(handle, foci, amps, std::ptr::null(), size)
+}
+
+/// Same as `GHR_Naive` with the weight of each focus. `weights` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_NaiveWeighted(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+) -> bool {
+    let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
+    let len = size as usize;
+    let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
+    let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
+    let mut naive = Naive::new();
+    naive.set_target_foci(foci);
+    naive.set_target_amps(amps);
+    if let Some(weights) = weights {
+        naive.set_target_weights(weights);
+    }
+    let res = naive.optimize((*calc).wave_sources());
+    forget(calc);
+    res.is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn GHR_GS(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    size: u64,
+    repeat: u64,
+) -> bool {
+    GHR_GSWeighted(handle, foci, amps, std::ptr::null(), size, repeat)
+}
+
+/// Same as `GHR_GS` with the weight of each focus. `weights` may be null.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_GSWeighted(
+    handle: *mut c_void,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    repeat: u64,
+) -> bool {
+    let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
+    let len = size as usize;
+    let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
+    let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
+    let mut gs = GS::new(repeat as _);
+    gs.set_target_foci(foci);
+    gs.set_target_amps(amps);
+    if let Some(weights) = weights {
+        gs.set_target_weights(weights);
+    }
+    let res = gs.optimize((*calc).wave_sources());
     forget(calc);
+    res.is_ok()
+}
+
+/// Optimize with the optimizer built from `spec`, a JSON string such as `{ "kind": "gspat", "repeat": 100 }`.
+/// Returns false if `spec` is invalid or the optimization fails, whose reason is given by `GHR_LastError`.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_Optimize(
+    handle: *mut c_void,
+    spec: *const c_char,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+) -> bool {
+    optimize_with_spec(
+        handle,
+        spec,
+        foci,
+        amps,
+        weights,
+        size,
+        None,
+        Monitor::new(),
+        Propagation::Analytic,
+    )
+}
+
+/// Called with the iteration, the cost, the elapsed time in seconds and `user_data`. Returns false to stop.
+pub type ProgressCallback = extern "C" fn(u64, Float, Float, *mut c_void) -> bool;
+
+struct UserData(*mut c_void);
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// Same as `GHR_Optimize`, but `callback` is called at each iteration if not null,
+/// and the optimization stops after `time_budget` seconds if it is positive.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GHR_OptimizeWithCallback(
+    handle: *mut c_void,
+    spec: *const c_char,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    callback: Option<ProgressCallback>,
+    user_data: *mut c_void,
+    time_budget: Float,
+) -> bool {
+    let monitor = make_monitor(callback, user_data, time_budget);
+    optimize_with_spec(
+        handle,
+        spec,
+        foci,
+        amps,
+        weights,
+        size,
+        None,
+        monitor,
+        Propagation::Analytic,
+    )
+}
+
+/// Same as `GHR_OptimizeWithCallback`, but only the wave sources whose `mask` is true are optimized and the others are turned off.
+/// Returns false if `mask` is null or `mask_len`, the length of `mask`, is different from the number of the wave sources.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GHR_OptimizeMasked(
+    handle: *mut c_void,
+    spec: *const c_char,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    mask: *const bool,
+    mask_len: u64,
+    callback: Option<ProgressCallback>,
+    user_data: *mut c_void,
+    time_budget: Float,
+) -> bool {
+    if mask.is_null() {
+        set_last_error("mask is null".to_string());
+        return false;
+    }
+    let mask = std::slice::from_raw_parts(mask, mask_len as usize);
+    let monitor = make_monitor(callback, user_data, time_budget);
+    optimize_with_spec(
+        handle,
+        spec,
+        foci,
+        amps,
+        weights,
+        size,
+        Some(mask),
+        monitor,
+        Propagation::Analytic,
+    )
+}
+
+/// Same as `GHR_OptimizeMasked`, but optimize against `matrix` instead of the transfer functions computed from the positions.
+/// `matrix` is a row-major complex matrix with `rows` rows, the control points, and the columns for the wave sources,
+/// whose elements are the pairs of the real and imaginary parts. `mask` may be null, otherwise its length `mask_len` must be the number of the wave sources.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn GHR_OptimizeWithTransferMatrix(
+    handle: *mut c_void,
+    spec: *const c_char,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    mask: *const bool,
+    mask_len: u64,
+    callback: Option<ProgressCallback>,
+    user_data: *mut c_void,
+    time_budget: Float,
+    matrix: *const Float,
+    rows: u64,
+) -> bool {
+    let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
+    let cols = (*calc).wave_sources().len();
+    forget(calc);
+    let rows = rows as usize;
+    let data = std::slice::from_raw_parts(matrix as *const Complex, rows * cols);
+    let matrix = Array::from_shape_vec((rows, cols), data.to_vec()).unwrap();
+    let mask = if mask.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(mask, mask_len as usize))
+    };
+    // The columns of the masked sources are dropped by the optimizer
+    if let Some(mask) = mask {
+        if mask.len() != cols {
+            set_last_error(format!(
+                "mask has {} elements, but there are {} wave sources",
+                mask.len(),
+                cols
+            ));
+            return false;
+        }
+    }
+    let monitor = make_monitor(callback, user_data, time_budget);
+    optimize_with_spec(
+        handle,
+        spec,
+        foci,
+        amps,
+        weights,
+        size,
+        mask,
+        monitor,
+        Propagation::imported(matrix),
+    )
+}
+
+fn make_monitor(
+    callback: Option<ProgressCallback>,
+    user_data: *mut c_void,
+    time_budget: Float,
+) -> Monitor {
+    let mut monitor = Monitor::new();
+    if let Some(callback) = callback {
+        let user_data = UserData(user_data);
+        monitor = monitor.with_observer(move |progress| {
+            let user_data = &user_data;
+            if callback(
+                progress.iteration as u64,
+                progress.cost,
+                progress.elapsed.as_secs_f64(),
+                user_data.0,
+            ) {
+                Control::Continue
+            } else {
+                Control::Stop
+            }
+        });
+    }
+    if time_budget > 0. {
+        monitor = monitor.with_time_budget(Duration::from_secs_f64(time_budget));
+    }
+    monitor
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn optimize_with_spec(
+    handle: *mut c_void,
+    spec: *const c_char,
+    foci: *const c_void,
+    amps: *const Float,
+    weights: *const Float,
+    size: u64,
+    mask: Option<&[bool]>,
+    monitor: Monitor,
+    propagation: Propagation,
+) -> bool {
+    let spec = match CStr::from_ptr(spec)
+        .to_str()
+        .map_err(|e| e.to_string())
+        .and_then(|spec| OptimizerSpec::from_json(spec).map_err(|e| e.to_string()))
+    {
+        Ok(spec) => spec,
+        Err(e) => {
+            set_last_error(format!("invalid spec: {}", e));
+            return false;
+        }
+    };
+    let mut calc: Box<CpuCalculator> = Box::from_raw(handle as *mut _);
+    let len = size as usize;
+    let foci = std::slice::from_raw_parts(foci as *mut Vector3, len);
+    let amps = std::slice::from_raw_parts(amps, len);
+    let weights = if weights.is_null() {
+        None
+    } else {
+        Some(std::slice::from_raw_parts(weights, len))
+    };
+    let mut optimizer = spec.build_with(monitor, propagation);
+    optimizer.set_target_foci(foci);
+    optimizer.set_target_amps(amps);
+    if let Some(weights) = weights {
+        optimizer.set_target_weights(weights);
+    }
+    let calibration = (*calc).calibration().cloned();
+    let wave_sources = (*calc).wave_sources();
+    let res = match (mask, &calibration) {
+        (Some(mask), Some(calibration)) => {
+            optimizer.optimize_masked_calibrated(wave_sources, mask, calibration)
+        }
+        (Some(mask), None) => optimizer.optimize_masked(wave_sources, mask),
+        (None, Some(calibration)) => optimizer.optimize_calibrated(wave_sources, calibration),
+        (None, None) => optimizer.optimize(wave_sources),
+    };
+    forget(calc);
+    match res {
+        Ok(_) => {
+            set_last_error(String::new());
+            true
+        }
+        Err(e) => {
+            set_last_error(e.to_string());
+            false
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+fn set_last_error(message: String) {
+    LAST_ERROR.with(|e| *e.borrow_mut() = message);
+}
+
+unsafe fn write_str(s: &str, buf: *mut c_char, len: u64) -> u64 {
+    let required = s.len() + 1;
+    if !buf.is_null() && len as usize >= required {
+        std::ptr::copy_nonoverlapping(s.as_ptr() as *const c_char, buf, s.len());
+        *buf.add(s.len()) = 0;
+    }
+    required as u64
+}
+
+/// Write the JSON list of the available algorithms with their parameters into `buf` if it is not null.
+/// Returns the length of the JSON string including the terminating null.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_Algorithms(buf: *mut c_char, len: u64) -> u64 {
+    write_str(&serde_json::to_string(&algorithms()).unwrap(), buf, len)
+}
+
+/// Write the reason why the last `GHR_Optimize*` call on this thread failed into `buf` in the same way as `GHR_Algorithms`.
+/// The message is empty if the call succeeded.
+#[no_mangle]
+pub unsafe extern "C" fn GHR_LastError(buf: *mut c_char, len: u64) -> u64 {
+    LAST_ERROR.with(|e| write_str(&e.borrow(), buf, len))
 }
@@ -0,0 +1,86 @@
+/*
+ * File: calibration.rs
+ * Project: examples
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use ghr::{buffer::FieldBuffer, calibration::*, wave_source::WaveSource, Complex, Float, PI};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const NUM_SOURCE_X: usize = 18;
+const NUM_SOURCE_Y: usize = 14;
+const SOURCE_SIZE: Float = 10.16;
+
+fn main() {
+    let mut transducers = Vec::new();
+    for y in 0..NUM_SOURCE_Y {
+        for x in 0..NUM_SOURCE_X {
+            let pos = [SOURCE_SIZE * x as Float, SOURCE_SIZE * y as Float, 0.];
+            transducers.push(WaveSource::new(pos, Complex::new(0., 0.)));
+        }
+    }
+    let n = transducers.len();
+
+    // Phase offsets within +-30 deg and gains within 0.8-1.0
+    let mut rng = StdRng::seed_from_u64(0);
+    let phase_offsets: Vec<Float> = (0..n)
+        .map(|_| (rng.gen::<Float>() - 0.5) * PI / 3.)
+        .collect();
+    let gains: Vec<Float> = (0..n).map(|_| 0.8 + 0.2 * rng.gen::<Float>()).collect();
+    let truth = Calibration::new(&phase_offsets, &gains);
+
+    // Microphone on a 8x8 grid on the plane 150 mm above the array
+    let center_x = SOURCE_SIZE * (NUM_SOURCE_X - 1) as Float / 2.0;
+    let center_y = SOURCE_SIZE * (NUM_SOURCE_Y - 1) as Float / 2.0;
+    let mut points = vec![];
+    for y in 0..8 {
+        for x in 0..8 {
+            points.push([
+                center_x + 20. * (x as Float - 3.5),
+                center_y + 20. * (y as Float - 3.5),
+                150.,
+            ]);
+        }
+    }
+
+    let estimator = CalibrationEstimator::new();
+    for &noise in &[0., 1e-4, 1e-3] {
+        let simulator = MeasurementSimulator::new(truth.clone())
+            .with_noise(noise)
+            .with_seed(0);
+        let drives = simulator.random_drives(n, 16);
+        let measurements = simulator.measure(&transducers, &drives, &points);
+        if noise == 0. {
+            let rms = measurements
+                .iter()
+                .flat_map(|m| m.field.buffer().iter())
+                .map(|p| p.norm_sqr())
+                .sum::<Float>()
+                / (measurements.len() * points.len()) as Float;
+            println!("RMS of the measured field: {:.4}", rms.sqrt());
+        }
+        let estimated = estimator.estimate(&transducers, &measurements).unwrap();
+
+        let mut phase_error: Float = 0.;
+        let mut gain_error: Float = 0.;
+        for j in 0..n {
+            let d = Complex::new(0., estimated.phase_offsets()[j] - phase_offsets[j]).exp();
+            phase_error = phase_error.max(d.arg().abs());
+            gain_error = gain_error.max((estimated.gains()[j] - gains[j]).abs());
+        }
+        println!(
+            "noise: {}, max phase error: {:.3} deg, max gain error: {:.4}",
+            noise,
+            phase_error.to_degrees(),
+            gain_error
+        );
+    }
+}
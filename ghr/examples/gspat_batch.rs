@@ -0,0 +1,81 @@
+/*
+ * File: gspat_batch.rs
+ * Project: examples
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use ghr::{optimizer::*, wave_source::WaveSource, Complex, Float, PI};
+
+use std::time::Instant;
+
+const NUM_SOURCE_X: usize = 18;
+const NUM_SOURCE_Y: usize = 14;
+const SOURCE_SIZE: Float = 10.16;
+
+fn main() {
+    // Two foci moving on a circle with 10 kHz update rate and 20 Hz rotation, i.e., 500 frames per cycle
+    let frame_per_cycle = 500;
+    let cycles = 10;
+    let radius = 20.0;
+
+    let center = [
+        SOURCE_SIZE * (NUM_SOURCE_X - 1) as Float / 2.0,
+        SOURCE_SIZE * (NUM_SOURCE_Y - 1) as Float / 2.0,
+        150.0,
+    ];
+
+    let mut transducers = Vec::new();
+    for y in 0..NUM_SOURCE_Y {
+        for x in 0..NUM_SOURCE_X {
+            let pos = [SOURCE_SIZE * x as Float, SOURCE_SIZE * y as Float, 0.];
+            transducers.push(WaveSource::new(pos, Complex::new(0., 0.)));
+        }
+    }
+
+    let frames: Vec<_> = (0..frame_per_cycle * cycles)
+        .map(|k| {
+            let theta = 2.0 * PI * (k % frame_per_cycle) as Float / frame_per_cycle as Float;
+            let (s, c) = theta.sin_cos();
+            let mut targets = Targets::new();
+            targets.set_foci(&[
+                [center[0] + radius * c, center[1] + radius * s, center[2]],
+                [center[0] - radius * c, center[1] - radius * s, center[2]],
+            ]);
+            targets.set_amps(&[1.0, 1.0]);
+            targets
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut optimizer = GSPAT::new(100);
+    for targets in &frames {
+        *optimizer.targets_mut() = targets.clone();
+        optimizer.optimize(&mut transducers).unwrap();
+    }
+    println!(
+        "GSPAT: {:.0} fps",
+        frames.len() as Float / start.elapsed().as_secs_f64()
+    );
+
+    // Keep the propagators of a whole cycle
+    let mut batch = GSPATBatch::new(&transducers, 100).with_capacity(frame_per_cycle);
+    let report = batch.optimize(&frames).unwrap();
+    println!(
+        "GSPATBatch (first): {:.0} fps, {} propagators built",
+        report.fps(),
+        report.propagators_built
+    );
+    let report = batch.optimize(&frames).unwrap();
+    println!(
+        "GSPATBatch (cached): {:.0} fps, {} propagators built",
+        report.fps(),
+        report.propagators_built
+    );
+}
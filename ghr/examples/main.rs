@@ -102,7 +102,7 @@ fn main() {
     let mut optimizer = GreedyBruteForce::new(16, 16, false);
     optimizer.set_target_foci(&target_pos);
     optimizer.set_target_amps(&amps);
-    optimizer.optimize(calculator.wave_sources());
+    optimizer.optimize(calculator.wave_sources()).unwrap();
     buffer.calculate(&calculator);
     println!("GBS: {}", buffer.max());
     write_image!("img/xy_gbs.png", buffer, bb);
@@ -110,7 +110,7 @@ fn main() {
     let mut horn = Horn::new(1000, 1e-3, 0.9);
     horn.set_target_foci(&target_pos);
     horn.set_target_amps(&amps);
-    horn.optimize(calculator.wave_sources());
+    horn.optimize(calculator.wave_sources()).unwrap();
     buffer.calculate(&calculator);
     println!("HORN: {}", buffer.max());
     write_image!("img/xy_horn.png", buffer, bb);
@@ -118,7 +118,7 @@ fn main() {
     let mut long = Long::new(1.0);
     long.set_target_foci(&target_pos);
     long.set_target_amps(&amps);
-    long.optimize(calculator.wave_sources());
+    long.optimize(calculator.wave_sources()).unwrap();
     buffer.calculate(&calculator);
     println!("LONG: {}", buffer.max());
     write_image!("img/xy_long.png", buffer, bb);
@@ -126,7 +126,7 @@ fn main() {
     let mut lm = LM::new(1e-8, 1e-8, 1e-3, 200);
     lm.set_target_foci(&target_pos);
     lm.set_target_amps(&amps);
-    lm.optimize(calculator.wave_sources());
+    lm.optimize(calculator.wave_sources()).unwrap();
     buffer.calculate(&calculator);
     println!("LM: {}", buffer.max());
     write_image!("img/xy_lm.png", buffer, bb);
@@ -134,7 +134,7 @@ fn main() {
     let mut gspat = GSPAT::new(100);
     gspat.set_target_foci(&target_pos);
     gspat.set_target_amps(&amps);
-    gspat.optimize(calculator.wave_sources());
+    gspat.optimize(calculator.wave_sources()).unwrap();
     buffer.calculate(&calculator);
     println!("GS-PAT: {}", buffer.max());
     write_image!("img/xy_gspat.png", buffer, bb);
@@ -51,10 +51,7 @@ fn main() {
         let t = 2. * PI * i as Float / num as Float;
         target_pos.push(add(focal_pos, [rad * t.cos(), rad * t.sin(), 0.]));
     }
-    let mut amps = Vec::with_capacity(target_pos.len());
-    for _ in 0..target_pos.len() {
-        amps.push(1.0);
-    }
+    let amps = vec![1.0; target_pos.len()];
 
     // let mut optimizer = GreedyBruteForce::new(16, 1, false);
     // let mut optimizer = Horn::new(1000, 1e-3, 0.9);
@@ -64,20 +61,20 @@ fn main() {
     optimizer.set_target_foci(&target_pos);
     optimizer.set_target_amps(&amps);
 
-    optimizer.optimize(calculator.wave_sources());
+    optimizer.optimize(calculator.wave_sources()).unwrap();
 
     let iter = 10;
     let mut times = Vec::with_capacity(iter);
     for _ in 0..iter {
         let start = Instant::now();
-        optimizer.optimize(calculator.wave_sources());
+        optimizer.optimize(calculator.wave_sources()).unwrap();
         times.push(start.elapsed().as_micros());
     }
 
     let mut ave = 0;
     let mut stdv = 0;
     let mut max = 0;
-    let mut min = std::u128::MAX;
+    let mut min = u128::MAX;
 
     for &t in times.iter() {
         ave += t as i128;
@@ -78,9 +78,9 @@ fn calc_relative_error<T: Optimizer>(
     for &p in foci.iter() {
         buffer.add_observe_point(p, Complex::new(0., 0.));
     }
-    optimizer.set_target_foci(&foci);
-    optimizer.set_target_amps(&amps);
-    optimizer.optimize(calculator.wave_sources());
+    optimizer.set_target_foci(foci);
+    optimizer.set_target_amps(amps);
+    optimizer.optimize(calculator.wave_sources()).unwrap();
     buffer.calculate(calculator);
 
     let mut max_v = Float::NEG_INFINITY;
@@ -138,7 +138,7 @@ fn test<T: Optimizer>(
     amps_set: &[Vec<Float>],
 ) {
     let mut opt = opt;
-    let errors = relative_errors(&mut opt, calculator, &foci_set, &amps_set);
+    let errors = relative_errors(&mut opt, calculator, foci_set, amps_set);
     let mut wtr = csv::Writer::from_path(format!("relative_errors/{}_M{}.csv", name, m)).unwrap();
     write_data(&mut wtr, &errors);
     println!("\t{} done", name);
@@ -255,5 +255,16 @@ fn main() {
             &foci_set,
             &amps_set,
         );
+
+        test(
+            Naive::new(),
+            "naive",
+            m,
+            &mut calculator,
+            &foci_set,
+            &amps_set,
+        );
+
+        test(GS::new(100), "gs", m, &mut calculator, &foci_set, &amps_set);
     }
 }
@@ -50,7 +50,7 @@ fn calc_p1(focus: Vector3, n_sqrt: usize) -> Float {
         .generate::<Amplitude>();
 
     buffer.calculate(&calculator);
-    buffer.buffer()[0] as f64
+    buffer.buffer()[0]
 }
 
 fn generate_test_set(
@@ -118,7 +118,7 @@ fn measure_time<T: Optimizer>(
         opt.set_target_amps(amps);
 
         let start = Instant::now();
-        opt.optimize(calculator.wave_sources());
+        opt.optimize(calculator.wave_sources()).unwrap();
         elasped.push(start.elapsed().as_micros());
     }
 
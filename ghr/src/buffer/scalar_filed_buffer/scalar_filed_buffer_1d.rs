@@ -31,10 +31,7 @@ pub struct ScalarFieldBuffer1D {
 
 impl ScalarFieldBuffer1D {
     pub fn new(axis: Axis, bounds: Bounds, origin: Vector3, resolution: Float) -> Self {
-        let mut buffer = Vec::with_capacity(bounds.size());
-        unsafe {
-            buffer.set_len(bounds.size());
-        }
+        let buffer = vec![0.; bounds.size()];
         Self {
             axis,
             buffer,
@@ -31,10 +31,7 @@ pub struct ScalarFieldBuffer2D {
 
 impl ScalarFieldBuffer2D {
     pub fn new(dim: (Axis, Axis), bounds: Bounds, origin: Vector3, resolution: Float) -> Self {
-        let mut buffer = Vec::with_capacity(bounds.size());
-        unsafe {
-            buffer.set_len(bounds.size());
-        }
+        let buffer = vec![0.; bounds.size()];
         Self {
             dim,
             buffer,
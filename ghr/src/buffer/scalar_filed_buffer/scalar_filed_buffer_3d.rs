@@ -36,10 +36,7 @@ impl ScalarFieldBuffer3D {
         origin: Vector3,
         resolution: Float,
     ) -> Self {
-        let mut buffer = Vec::with_capacity(bounds.size());
-        unsafe {
-            buffer.set_len(bounds.size());
-        }
+        let buffer = vec![0.; bounds.size()];
         Self {
             dim,
             buffer,
@@ -11,11 +11,16 @@
  *
  */
 
+use std::sync::Arc;
+
+use ndarray::*;
 use rayon::prelude::*;
 
 use super::*;
 use crate::{
     buffer::{AmplitudeFieldBuffer, ComplexFieldBufferScatter, FieldBuffer, IntensityFieldBuffer},
+    calibration::Calibration,
+    transfer_matrix::{transfer_matrix, TransferCache},
     utils::transfer,
     wave_source::WaveSource,
     Complex,
@@ -23,11 +28,54 @@ use crate::{
 
 pub struct CpuCalculator {
     sources: Vec<WaveSource>,
+    transfer_cache: Option<Arc<TransferCache>>,
+    calibration: Option<Calibration>,
 }
 
 impl CpuCalculator {
     pub fn new() -> CpuCalculator {
-        CpuCalculator { sources: vec![] }
+        CpuCalculator {
+            sources: vec![],
+            transfer_cache: None,
+            calibration: None,
+        }
+    }
+
+    /// Calculate the field as the product of the transfer matrix taken from `cache` and the emissions,
+    /// which is faster when the field is calculated repeatedly on the same observe points.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.transfer_cache = Some(cache);
+        self
+    }
+
+    pub fn set_transfer_cache(&mut self, cache: Option<Arc<TransferCache>>) {
+        self.transfer_cache = cache;
+    }
+
+    /// Simulate the sources emitting their drives modified by `calibration`.
+    pub fn with_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = Some(calibration);
+        self
+    }
+
+    pub fn set_calibration(&mut self, calibration: Option<Calibration>) {
+        self.calibration = calibration;
+    }
+
+    pub fn calibration(&self) -> Option<&Calibration> {
+        self.calibration.as_ref()
+    }
+
+    fn emissions(&self) -> Vec<Complex> {
+        match &self.calibration {
+            Some(calibration) => self
+                .sources
+                .iter()
+                .enumerate()
+                .map(|(i, s)| calibration.emission(i, s.q))
+                .collect(),
+            None => self.sources.iter().map(|s| s.q).collect(),
+        }
     }
 }
 
@@ -39,18 +87,25 @@ impl std::default::Default for CpuCalculator {
 
 macro_rules! calc_from_complex_wave {
     ($val: ident, $exp: expr, $self: ident, $buffer: ident) => {{
-        $buffer
-            .observe_points()
-            .collect::<Vec<_>>()
-            .par_iter()
-            .map(|&observe_point| {
-                let mut $val = Complex::new(0., 0.);
-                for source in $self.sources.iter() {
-                    $val += source.q * transfer(source.pos, observe_point);
-                }
-                $exp
-            })
-            .collect_into_vec($buffer.buffer_mut());
+        let observe_points = $buffer.observe_points().collect::<Vec<_>>();
+        let q = $self.emissions();
+        match $self.transfer_cache.as_deref() {
+            Some(cache) => {
+                let matrix = transfer_matrix(Some(cache), &$self.sources, &observe_points);
+                let q = Array1::from(q);
+                *$buffer.buffer_mut() = matrix.matrix().dot(&q).iter().map(|&$val| $exp).collect();
+            }
+            None => observe_points
+                .par_iter()
+                .map(|&observe_point| {
+                    let mut $val = Complex::new(0., 0.);
+                    for (source, &q) in $self.sources.iter().zip(q.iter()) {
+                        $val += q * transfer(source.pos, observe_point);
+                    }
+                    $exp
+                })
+                .collect_into_vec($buffer.buffer_mut()),
+        }
     }};
 }
 
@@ -0,0 +1,240 @@
+/*
+ * File: estimator.rs
+ * Project: calibration
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{fmt, sync::Arc};
+
+use ndarray::*;
+use ndarray_linalg::{error::LinalgError, *};
+
+use crate::{
+    buffer::{ComplexFieldBufferScatter, FieldBuffer},
+    calibration::Calibration,
+    transfer_matrix::{transfer_matrix, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float,
+};
+
+#[derive(Debug)]
+pub enum CalibrationError {
+    /// No wave source to be calibrated.
+    NoWaveSource,
+    /// The drives of a measurement do not have a value for each wave source.
+    DriveSizeMismatch {
+        measurement: usize,
+        sources: usize,
+        len: usize,
+    },
+    /// The normal equations cannot be solved, e.g., due to too few or too similar drive patterns.
+    Linalg(LinalgError),
+}
+
+impl fmt::Display for CalibrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CalibrationError::NoWaveSource => write!(f, "No wave source to be calibrated"),
+            CalibrationError::DriveSizeMismatch {
+                measurement,
+                sources,
+                len,
+            } => write!(
+                f,
+                "Measurement {}: the number of wave sources ({}) and drives ({}) must be the same",
+                measurement, sources, len
+            ),
+            CalibrationError::Linalg(e) => write!(f, "Linear algebra error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CalibrationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CalibrationError::Linalg(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LinalgError> for CalibrationError {
+    fn from(e: LinalgError) -> Self {
+        CalibrationError::Linalg(e)
+    }
+}
+
+/// Complex pressures measured at the observe points of `field` while the sources are driven by `drives`
+pub struct Measurement {
+    pub drives: Vec<Complex>,
+    pub field: ComplexFieldBufferScatter,
+}
+
+impl Measurement {
+    pub fn new(drives: Vec<Complex>, field: ComplexFieldBufferScatter) -> Self {
+        Self { drives, field }
+    }
+}
+
+/// Estimates the calibration of the sources from measurements by linear least squares
+///
+/// The emission of each source is its drive multiplied by an unknown complex factor `gain * exp(i * phase_offset)`,
+/// so the measured field is linear in the factors through the transfer matrices.
+/// The number of measured values must be at least that of the sources, with drive patterns varied enough.
+pub struct CalibrationEstimator {
+    regularization: Float,
+    transfer_cache: Option<Arc<TransferCache>>,
+}
+
+impl CalibrationEstimator {
+    pub fn new() -> Self {
+        Self {
+            regularization: 0.,
+            transfer_cache: None,
+        }
+    }
+
+    /// Pull the factors toward the ideal one by the Tikhonov regularization with the weight `lambda`,
+    /// which stabilizes the estimation with few or noisy measurements. Defaults to 0.
+    pub fn with_regularization(mut self, lambda: Float) -> Self {
+        self.regularization = lambda;
+        self
+    }
+
+    /// Take the transfer matrices from `cache`, which is effective when the same points are measured under many drive patterns.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.transfer_cache = Some(cache);
+        self
+    }
+
+    #[allow(non_snake_case)]
+    pub fn estimate(
+        &self,
+        wave_source: &[WaveSource],
+        measurements: &[Measurement],
+    ) -> Result<Calibration, CalibrationError> {
+        let n = wave_source.len();
+        if n == 0 {
+            return Err(CalibrationError::NoWaveSource);
+        }
+
+        // Normal equations (A^H A + lambda I) c = A^H y + lambda 1, where A_pj = G_pj d_j
+        let mut AhA: Array2<Complex> = Array::zeros((n, n));
+        let mut Ahy: Array1<Complex> = Array::zeros(n);
+        for (i, measurement) in measurements.iter().enumerate() {
+            if measurement.drives.len() != n {
+                return Err(CalibrationError::DriveSizeMismatch {
+                    measurement: i,
+                    sources: n,
+                    len: measurement.drives.len(),
+                });
+            }
+            let points: Vec<_> = measurement.field.observe_points().collect();
+            let G = transfer_matrix(self.transfer_cache.as_deref(), wave_source, &points);
+            let d = Array1::from(measurement.drives.clone());
+            let A = &G.matrix() * &d;
+            let Ah = A.t().mapv(|a| a.conj());
+            AhA += &Ah.dot(&A);
+            Ahy += &Ah.dot(&ArrayView1::from(measurement.field.buffer()));
+        }
+        for j in 0..n {
+            AhA[[j, j]] += self.regularization;
+            Ahy[j] += self.regularization;
+        }
+
+        let c = AhA.solveh(&Ahy)?;
+        let phase_offsets: Vec<_> = c.iter().map(|c| c.arg()).collect();
+        let gains: Vec<_> = c.iter().map(|c| c.norm()).collect();
+        Ok(Calibration::new(&phase_offsets, &gains))
+    }
+}
+
+impl Default for CalibrationEstimator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{calibration::MeasurementSimulator, PI};
+
+    fn sources() -> Vec<WaveSource> {
+        (0..4)
+            .flat_map(|y| (0..4).map(move |x| [10.16 * x as Float, 10.16 * y as Float, 0.]))
+            .map(|pos| WaveSource::new(pos, Complex::new(0., 0.)))
+            .collect()
+    }
+
+    fn points() -> Vec<crate::Vector3> {
+        (0..4)
+            .flat_map(|y| {
+                (0..4).map(move |x| [20. * x as Float - 15., 20. * y as Float - 15., 100.])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip_with_simulator() {
+        let sources = sources();
+        let n = sources.len();
+        let phase_offsets: Vec<Float> = (0..n)
+            .map(|j| (j as Float / n as Float - 0.5) * PI / 3.)
+            .collect();
+        let gains: Vec<Float> = (0..n).map(|j| 0.8 + 0.2 * (j % 3) as Float / 2.).collect();
+        let truth = Calibration::new(&phase_offsets, &gains);
+
+        let simulator = MeasurementSimulator::new(truth)
+            .with_noise(1e-6)
+            .with_seed(1);
+        let drives = simulator.random_drives(n, 8);
+        let measurements = simulator.measure(&sources, &drives, &points());
+        let estimated = CalibrationEstimator::new()
+            .estimate(&sources, &measurements)
+            .unwrap();
+
+        assert_eq!(estimated.len(), n);
+        for j in 0..n {
+            let d = Complex::new(0., estimated.phase_offsets()[j] - phase_offsets[j]).exp();
+            assert!(d.arg().abs() < 1e-3, "phase offset of {}: {}", j, d.arg());
+            assert!(
+                (estimated.gains()[j] - gains[j]).abs() < 1e-3,
+                "gain of {}",
+                j
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_drives() {
+        let sources = sources();
+        let simulator =
+            MeasurementSimulator::new(Calibration::identity(sources.len())).with_seed(1);
+        let mut measurements = simulator.measure(
+            &sources,
+            &simulator.random_drives(sources.len(), 2),
+            &points(),
+        );
+        measurements[1].drives.pop();
+        match CalibrationEstimator::new().estimate(&sources, &measurements) {
+            Err(CalibrationError::DriveSizeMismatch {
+                measurement: 1,
+                sources: 16,
+                len: 15,
+            }) => (),
+            r => panic!("unexpected {:?}", r.map(|c| c.len())),
+        }
+        assert!(matches!(
+            CalibrationEstimator::new().estimate(&[], &measurements),
+            Err(CalibrationError::NoWaveSource)
+        ));
+    }
+}
@@ -0,0 +1,20 @@
+/*
+ * File: mod.rs
+ * Project: calibration
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+mod estimator;
+mod simulator;
+mod table;
+
+pub use estimator::{CalibrationError, CalibrationEstimator, Measurement};
+pub use simulator::MeasurementSimulator;
+pub use table::Calibration;
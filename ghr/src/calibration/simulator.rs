@@ -0,0 +1,141 @@
+/*
+ * File: simulator.rs
+ * Project: calibration
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    buffer::{ComplexFieldBufferScatter, FieldBuffer},
+    calculator::{Calculate, Calculator, CpuCalculator},
+    calibration::{Calibration, Measurement},
+    wave_source::WaveSource,
+    Complex, Float, Vector3, PI,
+};
+
+/// Generates measurements of sources with a known calibration for testing the estimation offline
+pub struct MeasurementSimulator {
+    calibration: Calibration,
+    noise: Float,
+    seed: Option<u64>,
+}
+
+impl MeasurementSimulator {
+    pub fn new(calibration: Calibration) -> Self {
+        Self {
+            calibration,
+            noise: 0.,
+            seed: None,
+        }
+    }
+
+    /// Add complex Gaussian noise with the RMS amplitude `noise` to each measured value.
+    pub fn with_noise(mut self, noise: Float) -> Self {
+        self.noise = noise;
+        self
+    }
+
+    /// Make the drive patterns and the noise reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// `count` patterns driving `n` sources with the amplitude of 1 and random phases
+    pub fn random_drives(&self, n: usize, count: usize) -> Vec<Vec<Complex>> {
+        let mut rng = self.rng(1);
+        (0..count)
+            .map(|_| {
+                (0..n)
+                    .map(|_| Complex::new(0., 2. * PI * rng.gen::<Float>()).exp())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Measure the field at `points` under each of `drives`.
+    pub fn measure(
+        &self,
+        wave_source: &[WaveSource],
+        drives: &[Vec<Complex>],
+        points: &[Vector3],
+    ) -> Vec<Measurement> {
+        let mut rng = self.rng(0);
+        let mut calculator = CpuCalculator::new().with_calibration(self.calibration.clone());
+        calculator.add_wave_sources(wave_source);
+        drives
+            .iter()
+            .map(|drive| {
+                for (source, &q) in calculator.wave_sources().iter_mut().zip(drive.iter()) {
+                    source.q = q;
+                }
+                let mut field = ComplexFieldBufferScatter::new();
+                for &p in points {
+                    field.add_observe_point(p, Complex::new(0., 0.));
+                }
+                field.calculate(&calculator);
+                for v in field.buffer_mut().iter_mut() {
+                    *v += self.gaussian(&mut rng);
+                }
+                Measurement::new(drive.clone(), field)
+            })
+            .collect()
+    }
+
+    fn rng(&self, stream: u64) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(stream)),
+            None => StdRng::from_entropy(),
+        }
+    }
+
+    // Box-Muller transform
+    fn gaussian<R: Rng>(&self, rng: &mut R) -> Complex {
+        if self.noise <= 0. {
+            return Complex::new(0., 0.);
+        }
+        let u: Float = 1. - rng.gen::<Float>();
+        let v: Float = rng.gen();
+        let r = self.noise * (-u.ln()).sqrt();
+        Complex::new(0., 2. * PI * v).exp() * r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seed_makes_measurements_reproducible() {
+        let wave_source: Vec<_> = (0..4)
+            .map(|i| WaveSource::new([i as Float * 10., 0., 0.], Complex::new(0., 0.)))
+            .collect();
+        let points = [[0., 0., 100.], [10., 0., 100.]];
+        let simulator = MeasurementSimulator::new(Calibration::identity(4))
+            .with_noise(1e-3)
+            .with_seed(7);
+
+        let drives = simulator.random_drives(4, 3);
+        assert_eq!(drives, simulator.random_drives(4, 3));
+        assert!(drives
+            .iter()
+            .flatten()
+            .all(|d| (d.norm() - 1.).abs() < 1e-12));
+
+        let a = simulator.measure(&wave_source, &drives, &points);
+        let b = simulator.measure(&wave_source, &drives, &points);
+        assert_eq!(a.len(), 3);
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_eq!(a.drives, b.drives);
+            assert_eq!(a.field.buffer(), b.field.buffer());
+        }
+    }
+}
@@ -0,0 +1,198 @@
+/*
+ * File: table.rs
+ * Project: calibration
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    load::{parse_csv, LoadError},
+    wave_source::WaveSource,
+    Complex, Float,
+};
+
+/// Phase offset and gain of each wave source
+///
+/// A source driven by `q` emits `gain * exp(i * phase_offset) * q`.
+/// The sources beyond the table are regarded as ideal, i.e., with no phase offset and the gain of 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    phase_offsets: Vec<Float>,
+    gains: Vec<Float>,
+}
+
+impl Calibration {
+    /// # Panics
+    ///
+    /// Panics if the lengths of `phase_offsets` and `gains` are different.
+    pub fn new(phase_offsets: &[Float], gains: &[Float]) -> Self {
+        assert_eq!(
+            phase_offsets.len(),
+            gains.len(),
+            "The numbers of phase offsets and gains must be the same"
+        );
+        Self {
+            phase_offsets: phase_offsets.to_vec(),
+            gains: gains.to_vec(),
+        }
+    }
+
+    /// Calibration of `n` ideal sources
+    pub fn identity(n: usize) -> Self {
+        Self::new(&vec![0.; n], &vec![1.; n])
+    }
+
+    /// Load the table from a CSV file, where each line has the phase offset in radians and the gain of a source.
+    pub fn from_csv<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Self::parse_csv(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse_csv(text: &str) -> Result<Self, LoadError> {
+        let rows = parse_csv(text, Some(2))?;
+        Ok(Self {
+            phase_offsets: rows.iter().map(|r| r[0]).collect(),
+            gains: rows.iter().map(|r| r[1]).collect(),
+        })
+    }
+
+    /// Write the table in the format read by `from_csv`.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "phase_offset,gain")?;
+        for (offset, gain) in self.phase_offsets.iter().zip(self.gains.iter()) {
+            writeln!(writer, "{},{}", offset, gain)?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.gains.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gains.is_empty()
+    }
+
+    pub fn phase_offsets(&self) -> &[Float] {
+        &self.phase_offsets
+    }
+
+    pub fn gains(&self) -> &[Float] {
+        &self.gains
+    }
+
+    /// Ratio of the emission to the drive of the `i`-th source
+    pub fn factor(&self, i: usize) -> Complex {
+        match (self.phase_offsets.get(i), self.gains.get(i)) {
+            (Some(&offset), Some(&gain)) => gain * Complex::new(0., offset).exp(),
+            _ => Complex::new(1., 0.),
+        }
+    }
+
+    /// Emission of the `i`-th source driven by `drive`
+    pub fn emission(&self, i: usize, drive: Complex) -> Complex {
+        drive * self.factor(i)
+    }
+
+    /// Replace the drives of `wave_source` by their emissions.
+    pub fn emit(&self, wave_source: &mut [WaveSource]) {
+        for (i, source) in wave_source.iter_mut().enumerate() {
+            source.q = self.emission(i, source.q);
+        }
+    }
+
+    /// Replace the emissions of `wave_source` by the drives producing them, inverse of `emit`.
+    ///
+    /// A source with zero gain is not driven. The emission of each source should be bounded by its gain,
+    /// as `Optimizer::optimize_calibrated` does, otherwise the drive exceeding 1 is clamped to 1.
+    pub fn compensate(&self, wave_source: &mut [WaveSource]) {
+        for (i, source) in wave_source.iter_mut().enumerate() {
+            let factor = self.factor(i);
+            source.q = if factor.norm_sqr() > 0. {
+                source.q / factor
+            } else {
+                Complex::new(0., 0.)
+            };
+            let drive = source.q.norm_sqr().sqrt();
+            if drive > 1. {
+                source.q /= drive;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(qs: &[Complex]) -> Vec<WaveSource> {
+        qs.iter()
+            .enumerate()
+            .map(|(i, &q)| WaveSource::new([i as Float * 10., 0., 0.], q))
+            .collect()
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let calibration = Calibration::new(&[0.5, -1.25], &[1., 0.75]);
+        let mut buf = vec![];
+        calibration.write_csv(&mut buf).unwrap();
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.starts_with("phase_offset,gain\n"));
+        assert_eq!(Calibration::parse_csv(&text).unwrap(), calibration);
+        assert!(matches!(
+            Calibration::parse_csv("0.5,1,2\n"),
+            Err(LoadError::ColumnMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn sources_beyond_table_are_ideal() {
+        let calibration = Calibration::new(&[1.], &[0.5]);
+        assert_eq!(calibration.factor(1), Complex::new(1., 0.));
+        let e = calibration.emission(0, Complex::new(1., 0.));
+        assert!((e - Complex::new(0., 1.).exp() * 0.5).norm() < 1e-12);
+    }
+
+    #[test]
+    fn compensate_inverts_emit() {
+        let calibration = Calibration::new(&[0.3, -0.7, 0.], &[0.8, 0.9, 1.]);
+        let drives = [
+            Complex::new(0.5, 0.),
+            Complex::new(0., 0.9),
+            Complex::new(0.6, 0.8),
+        ];
+        let mut wave_source = sources(&drives);
+        calibration.emit(&mut wave_source);
+        calibration.compensate(&mut wave_source);
+        for (s, d) in wave_source.iter().zip(drives.iter()) {
+            assert!((s.q - d).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn compensate_bounds_each_drive() {
+        let calibration = Calibration::new(&[0., 0., 0.], &[0.5, 0., 1.]);
+        let mut wave_source = sources(&[
+            Complex::new(1., 0.),
+            Complex::new(1., 0.),
+            Complex::new(0.25, 0.),
+        ]);
+        calibration.compensate(&mut wave_source);
+        // Only the first drive exceeds 1, and the others are not scaled with it
+        assert!((wave_source[0].q - Complex::new(1., 0.)).norm() < 1e-12);
+        assert_eq!(wave_source[1].q, Complex::new(0., 0.));
+        assert!((wave_source[2].q - Complex::new(0.25, 0.)).norm() < 1e-12);
+    }
+}
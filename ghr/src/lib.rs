@@ -16,9 +16,12 @@ extern crate itertools;
 
 pub mod buffer;
 pub mod calculator;
+pub mod calibration;
 pub mod consts;
+pub mod load;
 pub mod math_utils;
 pub mod optimizer;
+pub mod transfer_matrix;
 pub mod utils;
 pub mod wave_source;
 
@@ -0,0 +1,331 @@
+/*
+ * File: load.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{convert::TryInto, fmt, io};
+
+use ndarray::*;
+
+use crate::{Complex, Float};
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    /// A line of the file cannot be parsed.
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The number of columns of a line is different from the expected one.
+    ColumnMismatch {
+        line: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// The file is not in the supported format.
+    Format(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "IO error: {}", e),
+            LoadError::Parse { line, message } => write!(f, "Line {}: {}", line, message),
+            LoadError::ColumnMismatch {
+                line,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Line {}: {} columns are expected, but {} columns are given",
+                line, expected, actual
+            ),
+            LoadError::Format(message) => write!(f, "Unsupported format: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
+
+/// Parse comma-separated numbers with `columns` columns in each line.
+/// If `columns` is `None`, all lines must have the same number of columns as the first one.
+///
+/// Empty lines and lines starting with `#` are skipped. The first line is skipped as a header if none of its fields is numeric.
+pub(crate) fn parse_csv(text: &str, columns: Option<usize>) -> Result<Vec<Vec<Float>>, LoadError> {
+    let mut rows = vec![];
+    let mut header = true;
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<Result<Float, _>> = line.split(',').map(|v| v.trim().parse()).collect();
+        if header && fields.iter().all(Result::is_err) {
+            header = false;
+            continue;
+        }
+        header = false;
+        let row = fields
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| LoadError::Parse {
+                line: i + 1,
+                message: e.to_string(),
+            })?;
+        let expected = columns.or_else(|| rows.first().map(Vec::len));
+        match expected {
+            Some(expected) if expected != row.len() => {
+                return Err(LoadError::ColumnMismatch {
+                    line: i + 1,
+                    expected,
+                    actual: row.len(),
+                })
+            }
+            _ => (),
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Parse a complex matrix in CSV, where each line is a row with the real and imaginary parts of the elements alternately.
+pub(crate) fn parse_complex_csv(text: &str) -> Result<Array2<Complex>, LoadError> {
+    let rows = parse_csv(text, None)?;
+    let cols = rows.first().map_or(0, Vec::len);
+    if cols % 2 != 0 {
+        return Err(LoadError::Format(
+            "the real and imaginary parts must be in pairs".to_string(),
+        ));
+    }
+    let data: Vec<_> = rows
+        .iter()
+        .flat_map(|r| r.chunks(2).map(|c| Complex::new(c[0], c[1])))
+        .collect();
+    Array::from_shape_vec((rows.len(), cols / 2), data)
+        .map_err(|e| LoadError::Format(e.to_string()))
+}
+
+/// Parse a 2-dimensional array in the NumPy `.npy` format.
+/// Complex and real arrays of little-endian 64-bit and 32-bit floating-point numbers are supported.
+pub(crate) fn parse_npy(bytes: &[u8]) -> Result<Array2<Complex>, LoadError> {
+    let format = |message: &str| LoadError::Format(message.to_string());
+    if bytes.len() < 10 || &bytes[0..6] != b"\x93NUMPY" {
+        return Err(format("not a npy file"));
+    }
+    let (header_len, offset) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize,
+            12,
+        ),
+        _ => return Err(format("unknown npy version")),
+    };
+    let header = bytes
+        .get(offset..offset + header_len)
+        .and_then(|h| std::str::from_utf8(h).ok())
+        .ok_or_else(|| format("broken header"))?;
+    let value = |key: &str| {
+        header
+            .find(&format!("'{}':", key))
+            .map(|i| header[i + key.len() + 3..].trim_start())
+            .ok_or_else(|| LoadError::Format(format!("'{}' is not found in the header", key)))
+    };
+
+    let descr = value("descr")?;
+    let descr = descr.trim_start_matches(&['\'', '"'][..]);
+    let (size, complex) = if descr.starts_with("<c16") {
+        (8, true)
+    } else if descr.starts_with("<c8") {
+        (4, true)
+    } else if descr.starts_with("<f8") {
+        (8, false)
+    } else if descr.starts_with("<f4") {
+        (4, false)
+    } else {
+        return Err(format("the data type must be complex or float"));
+    };
+    let fortran_order = value("fortran_order")?.starts_with("True");
+    let shape = value("shape")?;
+    let shape: Vec<usize> = shape
+        .get(1..shape.find(')').unwrap_or(0))
+        .unwrap_or("")
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| format("broken shape")))
+        .collect::<Result<_, _>>()?;
+    if shape.len() != 2 {
+        return Err(format("the array must be 2-dimensional"));
+    }
+
+    let data = &bytes[offset + header_len..];
+    let values = if complex { 2 } else { 1 };
+    let len = shape[0]
+        .checked_mul(shape[1])
+        .ok_or_else(|| format("the shape is too large"))?;
+    let bytes_len = len
+        .checked_mul(values * size)
+        .ok_or_else(|| format("the shape is too large"))?;
+    if data.len() < bytes_len {
+        return Err(format("the data is shorter than the shape"));
+    }
+    let float = |k: usize| -> Float {
+        let b = &data[k * size..(k + 1) * size];
+        match size {
+            8 => f64::from_le_bytes(b.try_into().unwrap()) as Float,
+            _ => f32::from_le_bytes(b.try_into().unwrap()) as Float,
+        }
+    };
+    let elements: Vec<_> = (0..len)
+        .map(|k| match complex {
+            true => Complex::new(float(2 * k), float(2 * k + 1)),
+            false => Complex::new(float(k), 0.),
+        })
+        .collect();
+    let matrix = if fortran_order {
+        Array::from_shape_vec((shape[1], shape[0]), elements).map(|m| m.reversed_axes())
+    } else {
+        Array::from_shape_vec((shape[0], shape[1]), elements)
+    };
+    matrix.map_err(|e| LoadError::Format(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npy(version: u8, descr: &str, fortran_order: bool, shape: &str, data: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{{'descr': '{}', 'fortran_order': {}, 'shape': {}, }}\n",
+            descr,
+            if fortran_order { "True" } else { "False" },
+            shape
+        );
+        let mut bytes = b"\x93NUMPY".to_vec();
+        bytes.extend_from_slice(&[version, 0]);
+        match version {
+            1 => bytes.extend_from_slice(&(header.len() as u16).to_le_bytes()),
+            _ => bytes.extend_from_slice(&(header.len() as u32).to_le_bytes()),
+        }
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn f64_bytes(values: &[f64]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn csv_skips_header_and_comments() {
+        let rows = parse_csv("a,b\n# comment\n\n1,2\n 3 , 4.5 \n", Some(2)).unwrap();
+        assert_eq!(rows, vec![vec![1., 2.], vec![3., 4.5]]);
+    }
+
+    #[test]
+    fn csv_reports_line_of_error() {
+        match parse_csv("1,2\n3\n", None) {
+            Err(LoadError::ColumnMismatch {
+                line: 2,
+                expected: 2,
+                actual: 1,
+            }) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_csv("1,2\n3,x\n", Some(2)) {
+            Err(LoadError::Parse { line: 2, .. }) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn csv_malformed_first_row_is_not_header() {
+        match parse_csv("1,x\n3,4\n", Some(2)) {
+            Err(LoadError::Parse { line: 1, .. }) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        match parse_csv("# comment\nx,1\n3,4\n", None) {
+            Err(LoadError::Parse { line: 2, .. }) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+    }
+
+    #[test]
+    fn complex_csv() {
+        let m = parse_complex_csv("1,2,3,4\n5,6,7,8\n").unwrap();
+        assert_eq!(m.dim(), (2, 2));
+        assert_eq!(m[[0, 1]], Complex::new(3., 4.));
+        assert_eq!(m[[1, 0]], Complex::new(5., 6.));
+        assert!(matches!(
+            parse_complex_csv("1,2,3\n"),
+            Err(LoadError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn npy_complex() {
+        let data = f64_bytes(&[1., 2., 3., 4., 5., 6., 7., 8., 9., 10., 11., 12.]);
+        let m = parse_npy(&npy(1, "<c16", false, "(2, 3)", &data)).unwrap();
+        assert_eq!(m.dim(), (2, 3));
+        assert_eq!(m[[0, 2]], Complex::new(5., 6.));
+        assert_eq!(m[[1, 0]], Complex::new(7., 8.));
+
+        let data: Vec<_> = [1f32, 2., 3., 4.]
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect();
+        let m = parse_npy(&npy(2, "<c8", false, "(1, 2)", &data)).unwrap();
+        assert_eq!(m[[0, 1]], Complex::new(3., 4.));
+    }
+
+    #[test]
+    fn npy_fortran_order() {
+        let data = f64_bytes(&[1., 2., 3., 4., 5., 6.]);
+        let m = parse_npy(&npy(1, "<f8", true, "(2, 3)", &data)).unwrap();
+        assert_eq!(m.dim(), (2, 3));
+        assert_eq!(m[[1, 0]], Complex::new(2., 0.));
+        assert_eq!(m[[0, 1]], Complex::new(3., 0.));
+    }
+
+    #[test]
+    fn npy_rejects_broken_files() {
+        let data = f64_bytes(&[1., 2.]);
+        for bytes in [
+            b"not a npy file".to_vec(),
+            npy(1, "<i8", false, "(1, 2)", &data),
+            npy(1, "<f8", false, "(2,)", &data),
+            npy(1, "<f8", false, "(2, 2)", &data),
+            npy(1, "<f8", false, "(18446744073709551615, 2)", &data),
+            npy(1, "<c16", false, "(4611686018427387904, 1)", &data),
+        ]
+        .iter()
+        {
+            assert!(matches!(parse_npy(bytes), Err(LoadError::Format(_))));
+        }
+    }
+}
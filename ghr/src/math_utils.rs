@@ -83,3 +83,13 @@ pub use float::*;
 pub fn c_norm(x: Complex) -> f64 {
     sqrt(x.norm_sqr())
 }
+
+/// `x / |x|`, or 1 if `x` is zero, e.g., the phase of the field at a focus not reached by any source
+pub fn c_unit(x: Complex) -> Complex {
+    let amp = c_norm(x);
+    if amp > 0. {
+        x / amp
+    } else {
+        Complex::new(1., 0.)
+    }
+}
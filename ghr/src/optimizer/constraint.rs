@@ -0,0 +1,166 @@
+/*
+ * File: constraint.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math_utils::c_norm, optimizer::OptimizeError, wave_source::WaveSource, Complex, Float,
+};
+
+/// Constraint on the amplitudes of the wave sources applied to the result of an optimizer
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AmplitudeConstraint {
+    /// All sources emit with the amplitude of 1, keeping their phases.
+    PhaseOnly,
+    /// Amplitudes larger than 1 are clamped to 1.
+    Clamp,
+    /// All amplitudes are divided by the largest one, so that the largest becomes 1.
+    NormalizeToMax,
+    /// All amplitudes are divided by the same factor only if some exceed 1, keeping their ratios.
+    UniformScale,
+    /// Amplitude of each source is clamped to its own maximum.
+    PerTransducerMax(Vec<Float>),
+}
+
+impl AmplitudeConstraint {
+    pub fn apply(&self, wave_source: &mut [WaveSource]) -> Result<(), OptimizeError> {
+        match self {
+            AmplitudeConstraint::PhaseOnly => {
+                for source in wave_source.iter_mut() {
+                    let amp = c_norm(source.q);
+                    source.q = if amp > 0. {
+                        source.q / amp
+                    } else {
+                        Complex::new(1., 0.)
+                    };
+                }
+            }
+            AmplitudeConstraint::Clamp => {
+                for source in wave_source.iter_mut() {
+                    Self::clamp(source, 1.0);
+                }
+            }
+            AmplitudeConstraint::NormalizeToMax => {
+                let max_coef = Self::max_amp(wave_source);
+                if max_coef > 0. {
+                    for source in wave_source.iter_mut() {
+                        source.q /= max_coef;
+                    }
+                }
+            }
+            AmplitudeConstraint::UniformScale => {
+                let max_coef = Self::max_amp(wave_source);
+                if max_coef > 1. {
+                    for source in wave_source.iter_mut() {
+                        source.q /= max_coef;
+                    }
+                }
+            }
+            AmplitudeConstraint::PerTransducerMax(limits) => {
+                if limits.len() != wave_source.len() {
+                    return Err(OptimizeError::SourceSizeMismatch {
+                        target: "amplitude limits",
+                        sources: wave_source.len(),
+                        len: limits.len(),
+                    });
+                }
+                // The exact norm is used, since the limits bound the drives compensating the calibration
+                for (source, &limit) in wave_source.iter_mut().zip(limits.iter()) {
+                    let amp = source.q.norm();
+                    if amp > limit {
+                        source.q *= limit / amp;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn clamp(source: &mut WaveSource, limit: Float) {
+        let amp = c_norm(source.q);
+        if amp > limit {
+            source.q = source.q / amp * limit;
+        }
+    }
+
+    fn max_amp(wave_source: &[WaveSource]) -> Float {
+        wave_source
+            .iter()
+            .fold(Float::NEG_INFINITY, |acc, s| acc.max(s.q.norm_sqr()))
+            .sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources(amps: &[Float]) -> Vec<WaveSource> {
+        amps.iter()
+            .map(|&a| WaveSource::new([0., 0., 0.], Complex::new(0., a)))
+            .collect()
+    }
+
+    fn amps(wave_source: &[WaveSource]) -> Vec<Float> {
+        wave_source.iter().map(|s| s.q.norm()).collect()
+    }
+
+    fn assert_amps(wave_source: &[WaveSource], expected: &[Float]) {
+        for (a, e) in amps(wave_source).iter().zip(expected.iter()) {
+            assert!(
+                (a - e).abs() < 1e-3,
+                "{:?} != {:?}",
+                amps(wave_source),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn constraints() {
+        let cases = [
+            (AmplitudeConstraint::PhaseOnly, [1., 1., 1.]),
+            (AmplitudeConstraint::Clamp, [0., 0.5, 1.]),
+            (AmplitudeConstraint::NormalizeToMax, [0., 0.25, 1.]),
+            (AmplitudeConstraint::UniformScale, [0., 0.25, 1.]),
+            (
+                AmplitudeConstraint::PerTransducerMax(vec![1., 0.25, 1.5]),
+                [0., 0.25, 1.5],
+            ),
+        ];
+        for (constraint, expected) in cases.iter() {
+            let mut wave_source = sources(&[0., 0.5, 2.]);
+            constraint.apply(&mut wave_source).unwrap();
+            assert_amps(&wave_source, expected);
+        }
+    }
+
+    #[test]
+    fn uniform_scale_keeps_amplitudes_within_limit() {
+        let mut wave_source = sources(&[0.2, 0.5]);
+        AmplitudeConstraint::UniformScale
+            .apply(&mut wave_source)
+            .unwrap();
+        assert_amps(&wave_source, &[0.2, 0.5]);
+    }
+
+    #[test]
+    fn per_transducer_max_checks_length() {
+        let mut wave_source = sources(&[0.5, 0.5]);
+        assert!(matches!(
+            AmplitudeConstraint::PerTransducerMax(vec![1.]).apply(&mut wave_source),
+            Err(OptimizeError::SourceSizeMismatch { len: 1, .. })
+        ));
+    }
+}
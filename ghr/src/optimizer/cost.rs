@@ -0,0 +1,124 @@
+/*
+ * File: cost.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{math_utils::c_norm, optimizer::OptimizeError, Complex, Float};
+
+pub type CostFn = dyn Fn(Complex, Float, Option<Complex>) -> Float + Send + Sync;
+
+/// Error between the field and the target at a focus
+///
+/// `L1`, `L2`, `Intensity` and `Relative` only see the amplitude, so the phases of the targets are ignored.
+#[derive(Clone, Default)]
+pub enum Cost {
+    /// |(|p| - a)|
+    L1,
+    /// (|p| - a)^2
+    L2,
+    /// |(|p|^2 - a^2)|
+    Intensity,
+    /// |(|p| - a)| / a
+    Relative,
+    /// |p - a exp(iφ)| if the phase φ is specified, otherwise the same as `L1`
+    #[default]
+    Complex,
+    /// User-defined error, which takes the field, the target amplitude and the target complex value if the phase is specified
+    Custom(Arc<CostFn>),
+}
+
+/// Built-in `Cost` without the user-defined one, which can be given by name in `OptimizerSpec`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostKind {
+    L1,
+    L2,
+    Intensity,
+    Relative,
+    #[default]
+    Complex,
+}
+
+impl From<CostKind> for Cost {
+    fn from(kind: CostKind) -> Self {
+        match kind {
+            CostKind::L1 => Cost::L1,
+            CostKind::L2 => Cost::L2,
+            CostKind::Intensity => Cost::Intensity,
+            CostKind::Relative => Cost::Relative,
+            CostKind::Complex => Cost::Complex,
+        }
+    }
+}
+
+impl Cost {
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: Fn(Complex, Float, Option<Complex>) -> Float + Send + Sync + 'static,
+    {
+        Cost::Custom(Arc::new(f))
+    }
+
+    /// Check that the cost is defined for the target amplitudes `amps`, i.e., that they are positive for `Relative`.
+    pub fn check(&self, amps: &[Float]) -> Result<(), OptimizeError> {
+        if let Cost::Relative = self {
+            if let Some((focus, &amp)) = amps.iter().enumerate().find(|(_, &amp)| amp <= 0.) {
+                return Err(OptimizeError::InvalidAmplitude { focus, amp });
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn error(&self, p: Complex, amp: Float, target: Option<Complex>) -> Float {
+        match self {
+            Cost::L1 => (c_norm(p) - amp).abs(),
+            Cost::L2 => {
+                let e = c_norm(p) - amp;
+                e * e
+            }
+            Cost::Intensity => (p.norm_sqr() - amp * amp).abs(),
+            Cost::Relative => (c_norm(p) - amp).abs() / amp,
+            Cost::Complex => match target {
+                Some(t) => c_norm(p - t),
+                None => (c_norm(p) - amp).abs(),
+            },
+            Cost::Custom(f) => f(p, amp, target),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_rejects_non_positive_amplitude() {
+        assert!(Cost::Relative.check(&[1., 0.5]).is_ok());
+        match Cost::Relative.check(&[1., 0.]) {
+            Err(OptimizeError::InvalidAmplitude { focus: 1, .. }) => (),
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(Cost::L1.check(&[0.]).is_ok());
+    }
+
+    #[test]
+    fn kind_by_name() {
+        let kind: CostKind = serde_json::from_str("\"relative\"").unwrap();
+        assert_eq!(kind, CostKind::Relative);
+        assert!(matches!(Cost::from(kind), Cost::Relative));
+        assert!(matches!(Cost::from(CostKind::default()), Cost::Complex));
+    }
+}
@@ -0,0 +1,138 @@
+/*
+ * File: error.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::fmt;
+
+use ndarray_linalg::error::LinalgError;
+
+use crate::Float;
+
+#[derive(Debug)]
+pub enum OptimizeError {
+    /// The number of foci and the length of other target values are different.
+    TargetSizeMismatch {
+        target: &'static str,
+        foci: usize,
+        len: usize,
+    },
+    /// The number of control points and the length of their values are different.
+    ControlPointSizeMismatch {
+        target: &'static str,
+        points: usize,
+        len: usize,
+    },
+    /// The number of wave sources and the length of their values are different.
+    SourceSizeMismatch {
+        target: &'static str,
+        sources: usize,
+        len: usize,
+    },
+    /// The optimizer requires more foci than given.
+    TooFewFoci { required: usize, actual: usize },
+    /// No wave source to be optimized.
+    NoWaveSource,
+    /// The optimizer does not support the kind of the control points, e.g., null points for `GSPAT`.
+    UnsupportedControlPoints {
+        optimizer: &'static str,
+        target: &'static str,
+    },
+    /// The optimizer ignores a part of the targets of the foci, e.g., the weights for `Horn`.
+    UnsupportedTarget {
+        optimizer: &'static str,
+        target: &'static str,
+    },
+    /// The cost is undefined for the target amplitude of the focus, e.g., zero for `Cost::Relative`.
+    InvalidAmplitude { focus: usize, amp: Float },
+    /// The imported transfer matrix does not have the rows for the control points or the columns for the wave sources.
+    TransferMatrixSizeMismatch {
+        rows: usize,
+        cols: usize,
+        points: usize,
+        sources: usize,
+    },
+    /// A linear algebra routine failed, e.g., due to a singular matrix.
+    Linalg(LinalgError),
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimizeError::TargetSizeMismatch { target, foci, len } => write!(
+                f,
+                "The number of foci ({}) and {} ({}) must be the same",
+                foci, target, len
+            ),
+            OptimizeError::ControlPointSizeMismatch {
+                target,
+                points,
+                len,
+            } => write!(
+                f,
+                "The number of control points ({}) and {} ({}) must be the same",
+                points, target, len
+            ),
+            OptimizeError::SourceSizeMismatch {
+                target,
+                sources,
+                len,
+            } => write!(
+                f,
+                "The number of wave sources ({}) and {} ({}) must be the same",
+                sources, target, len
+            ),
+            OptimizeError::TooFewFoci { required, actual } => write!(
+                f,
+                "At least {} foci are required, but {} foci are given",
+                required, actual
+            ),
+            OptimizeError::NoWaveSource => write!(f, "No wave source to be optimized"),
+            OptimizeError::UnsupportedControlPoints { optimizer, target } => {
+                write!(f, "{} does not support {}", optimizer, target)
+            }
+            OptimizeError::UnsupportedTarget { optimizer, target } => {
+                write!(f, "{} does not support {}", optimizer, target)
+            }
+            OptimizeError::InvalidAmplitude { focus, amp } => write!(
+                f,
+                "The amplitude of focus {} ({}) is not allowed by the cost",
+                focus, amp
+            ),
+            OptimizeError::TransferMatrixSizeMismatch {
+                rows,
+                cols,
+                points,
+                sources,
+            } => write!(
+                f,
+                "The transfer matrix ({}x{}) must have at least {} rows and {} columns",
+                rows, cols, points, sources
+            ),
+            OptimizeError::Linalg(e) => write!(f, "Linear algebra error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for OptimizeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OptimizeError::Linalg(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LinalgError> for OptimizeError {
+    fn from(e: LinalgError) -> Self {
+        OptimizeError::Linalg(e)
+    }
+}
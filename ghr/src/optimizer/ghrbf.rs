@@ -11,102 +11,628 @@
  *
  */
 
+use std::{sync::Arc, time::Instant};
+
 use crate::{
-    math_utils::*, optimizer::Optimizer, utils::transfer, wave_source::WaveSource, Complex, Float,
-    Vector3, PI,
+    math_utils::*,
+    optimizer::{
+        report::amplitude_error, AmplitudeConstraint, Convergence, Cost, Monitor, OptimizeError,
+        OptimizeReport, Optimizer, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float, Vector3, PI,
 };
-use ndarray::*;
+use rayon::prelude::*;
 
-pub struct GreedyBruteForce {
-    foci: Vec<Vector3>,
+/// Number of iterations of the golden-section search in the continuous refinement
+const GOLDEN_SECTION_ITER: usize = 24;
+
+/// Minimize `f` in `[lo, hi]` by the golden-section search, assuming `f` is unimodal in the interval
+fn golden_section<F: Fn(Float) -> Float>(f: F, mut lo: Float, mut hi: Float) -> Float {
+    let r = (Float::sqrt(5.0) - 1.0) / 2.0;
+    let mut x1 = hi - r * (hi - lo);
+    let mut x2 = lo + r * (hi - lo);
+    let mut f1 = f(x1);
+    let mut f2 = f(x2);
+    for _ in 0..GOLDEN_SECTION_ITER {
+        if f1 < f2 {
+            hi = x2;
+            x2 = x1;
+            f2 = f1;
+            x1 = hi - r * (hi - lo);
+            f1 = f(x1);
+        } else {
+            lo = x1;
+            x1 = x2;
+            f1 = f2;
+            x2 = lo + r * (hi - lo);
+            f2 = f(x2);
+        }
+    }
+    (lo + hi) / 2.0
+}
+
+/// Control points and their targets, flattened for the inner loop
+struct Problem<'a> {
+    // Control points are the foci, the null points and the upper-bound points in this order.
+    points: Vec<Vector3>,
+    m: usize,
+    m_null: usize,
     amps: Vec<Float>,
+    complex_amps: Vec<Option<Complex>>,
+    weights: Vec<Float>,
+    null_weights: &'a [Float],
+    bound_limits: &'a [Float],
+}
+
+impl<'a> Problem<'a> {
+    fn new(targets: &'a Targets) -> Self {
+        let points: Vec<_> = targets
+            .foci()
+            .iter()
+            .chain(targets.null_points().iter())
+            .chain(targets.bound_points().iter())
+            .copied()
+            .collect();
+        let m = targets.foci().len();
+        Self {
+            points,
+            m,
+            m_null: m + targets.null_points().len(),
+            amps: targets.amps().to_vec(),
+            complex_amps: (0..m).map(|i| targets.complex_amp(i)).collect(),
+            weights: (0..m).map(|i| targets.weight(i)).collect(),
+            null_weights: targets.null_weights(),
+            bound_limits: targets.bound_limits(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Cost when the source whose transfer is `g` emits `q` on top of the field `cache`
+    ///
+    /// The null points and the upper bounds are penalized by the squared errors as in `amplitude_error`.
+    fn eval(&self, cost: &Cost, g: &[Complex], cache: &[Complex], q: Complex) -> Float {
+        let mut v = 0.0;
+        for j in 0..self.m {
+            v += self.weights[j]
+                * cost.error(g[j] * q + cache[j], self.amps[j], self.complex_amps[j]);
+        }
+        for j in self.m..self.m_null {
+            v += self.null_weights[j - self.m] * (g[j] * q + cache[j]).norm_sqr();
+        }
+        for j in self.m_null..self.len() {
+            let e = (c_norm(g[j] * q + cache[j]) - self.bound_limits[j - self.m_null]).max(0.);
+            v += e * e;
+        }
+        v
+    }
+}
+
+pub struct GreedyBruteForce {
+    targets: Targets,
     phase_division: usize,
     amp_division: usize,
     randomize: bool,
+    seed: Option<u64>,
+    cost: Cost,
+    parallel: bool,
+    refine_passes: usize,
+    continuous: bool,
+    constraint: AmplitudeConstraint,
+    warm_start: bool,
+    propagation: Propagation,
+    monitor: Monitor,
 }
 
 impl GreedyBruteForce {
     pub fn new(phase_division: usize, amp_division: usize, randomize: bool) -> Self {
         Self {
-            foci: vec![],
-            amps: vec![],
+            targets: Targets::new(),
             phase_division,
             amp_division,
             randomize,
+            seed: None,
+            cost: Cost::default(),
+            parallel: false,
+            refine_passes: 0,
+            continuous: false,
+            constraint: AmplitudeConstraint::Clamp,
+            warm_start: false,
+            propagation: Propagation::Analytic,
+            monitor: Monitor::new(),
         }
     }
-}
 
-impl GreedyBruteForce {
-    #[allow(non_snake_case)]
-    pub fn optimize_amp_phase(&self, wave_sources: &mut [WaveSource]) {
-        let m = self.foci.len();
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
 
-        let mut cache: ArrayBase<OwnedRepr<Complex>, _> = Array::zeros(m);
-        let mut good_field = Array::zeros(m);
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
 
-        let mut amps = Array::zeros(m);
-        for i in 0..m {
-            amps[i] = self.amps[i];
-        }
+    /// See `Monitor`.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
 
+    /// Start the refinement from the current emissions of the wave sources instead of the greedy pass.
+    /// Useful with `with_refinement` when the targets change slightly from the previous optimization.
+    /// The warm start is ignored without the refinement, which would return the emissions unchanged.
+    pub fn with_warm_start(mut self, warm_start: bool) -> Self {
+        self.warm_start = warm_start;
+        self
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::Clamp` is used by default.
+    /// With `AmplitudeConstraint::PerTransducerMax`, the candidates of each source are scaled to its maximum during the search.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Make the random order of the sources reproducible.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set the error at each focus to be minimized. `Cost::Complex` is used by default.
+    /// The cost of the report is the squared error of the result as in the other optimizers, not this one.
+    pub fn with_cost(mut self, cost: Cost) -> Self {
+        self.cost = cost;
+        self
+    }
+
+    /// Evaluate the candidates of each source in parallel.
+    /// The result is identical to the sequential evaluation.
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// After the greedy pass, revisit all sources up to `passes` times.
+    /// Each source is removed from the field and chosen again with the other sources fixed.
+    /// The refinement stops early when a pass does not decrease the cost.
+    pub fn with_refinement(mut self, passes: usize) -> Self {
+        self.refine_passes = passes;
+        self
+    }
+
+    /// Refine the chosen phase and amplitude of each source continuously within half a division step on either side,
+    /// i.e., over the interval of the values rounded to the chosen candidate,
+    /// so that coarse divisions can be used without the quantization error.
+    pub fn with_continuous_refinement(mut self, continuous: bool) -> Self {
+        self.continuous = continuous;
+        self
+    }
+
+    /// Candidates of the emission of a source, in the order of the evaluation
+    fn candidates(&self) -> Vec<Complex> {
         let amp_step = Complex::new(1.0 / self.amp_division as Float, 0.);
         let phase_step = Complex::new(0.0, 2.0 * PI / self.phase_division as Float).exp();
+        let mut candidates = Vec::with_capacity(self.amp_division * self.phase_division);
+        for i in 1..=self.amp_division {
+            let mut q = i as Float * amp_step;
+            for _ in 0..self.phase_division {
+                candidates.push(q);
+                q *= phase_step;
+            }
+        }
+        candidates
+    }
+
+    /// Upper bound of the amplitude of each source during the search
+    fn limits(&self, n: usize) -> Result<Vec<Float>, OptimizeError> {
+        match &self.constraint {
+            AmplitudeConstraint::PerTransducerMax(limits) if limits.len() != n => {
+                Err(OptimizeError::SourceSizeMismatch {
+                    target: "amplitude limits",
+                    sources: n,
+                    len: limits.len(),
+                })
+            }
+            AmplitudeConstraint::PerTransducerMax(limits) => Ok(limits.clone()),
+            _ => Ok(vec![1.0; n]),
+        }
+    }
 
+    /// Returns the first candidate scaled by `limit` with the minimum cost, or `None` if no candidate has a finite cost.
+    fn choose(
+        &self,
+        problem: &Problem,
+        candidates: &[Complex],
+        g: &[Complex],
+        cache: &[Complex],
+        limit: Float,
+    ) -> Option<(usize, Float)> {
+        let eval = |(k, &q): (usize, &Complex)| {
+            let v = problem.eval(&self.cost, g, cache, q * limit);
+            (k, if v.is_nan() { Float::INFINITY } else { v })
+        };
+        // Ties are broken by the index so that the parallel evaluation picks the same candidate as the sequential one.
+        let better = |a: (usize, Float), b: (usize, Float)| {
+            if b.1 < a.1 || (b.1 == a.1 && b.0 < a.0) {
+                b
+            } else {
+                a
+            }
+        };
+        let identity = (usize::MAX, Float::INFINITY);
+        let (k, v) = if self.parallel {
+            candidates
+                .par_iter()
+                .enumerate()
+                .map(eval)
+                .reduce(|| identity, better)
+        } else {
+            candidates
+                .iter()
+                .enumerate()
+                .map(eval)
+                .fold(identity, better)
+        };
+        if v < Float::INFINITY {
+            Some((k, v))
+        } else {
+            None
+        }
+    }
+}
+
+impl GreedyBruteForce {
+    #[allow(non_snake_case)]
+    pub fn optimize_amp_phase(
+        &self,
+        wave_sources: &mut [WaveSource],
+    ) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_sources, 1)?;
+        self.cost.check(self.targets.amps())?;
+
+        let problem = Problem::new(&self.targets);
+        let l = problem.len();
+        let candidates = self.candidates();
+        let limits = self.limits(wave_sources.len())?;
+
+        // The sources are visited in a random order, not shuffled, so that their indices are kept
+        let mut order: Vec<_> = (0..wave_sources.len()).collect();
         if self.randomize {
-            let mut rng = rand::thread_rng();
-            use rand::seq::SliceRandom;
-            wave_sources.shuffle(&mut rng);
+            use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+            let mut rng = match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            };
+            order.shuffle(&mut rng);
         }
 
-        let mut g: ArrayBase<OwnedRepr<Complex>, _> = Array::zeros(m);
-        let mut gt: ArrayBase<OwnedRepr<Complex>, _> = Array::zeros(m);
-        for wave_source in wave_sources {
-            for i in 0..m {
-                g[i] = transfer(wave_source.pos, self.foci[i]);
+        let G = self.propagation.matrix(wave_sources, &problem.points)?;
+        let transfers: Vec<Vec<Complex>> = G
+            .matrix()
+            .gencolumns()
+            .into_iter()
+            .map(|g| g.to_vec())
+            .collect();
+
+        let mut cache = vec![Complex::new(0., 0.); l];
+        let mut cost = 0.0;
+        let mut iterations = 0;
+        let mut convergence = Convergence::Completed;
+        if self.warm_start && self.refine_passes > 0 {
+            for ((wave_source, g), &limit) in wave_sources
+                .iter_mut()
+                .zip(transfers.iter())
+                .zip(limits.iter())
+            {
+                let amp = wave_source.q.norm();
+                if amp > limit {
+                    wave_source.q *= limit / amp;
+                }
+                for j in 0..l {
+                    cache[j] += g[j] * wave_source.q;
+                }
+            }
+            let zero = vec![Complex::new(0., 0.); l];
+            cost = problem.eval(&self.cost, &zero, &cache, Complex::new(0., 0.));
+        } else {
+            for &k in order.iter() {
+                let (wave_source, g) = (&mut wave_sources[k], &transfers[k]);
+                cost = self.update(
+                    &problem,
+                    &candidates,
+                    g,
+                    &mut cache,
+                    wave_source,
+                    limits[k],
+                    None,
+                );
+                iterations += 1;
+                if let Some(c) = self.monitor.check(iterations, start, || cost) {
+                    convergence = c;
+                    break;
+                }
             }
-            let mut min_q = Complex::new(0., 0.);
-            let mut min_v = Float::INFINITY;
-            for i in 1..=self.amp_division {
-                let mut q = i as Float * amp_step;
-                for _ in 0..self.phase_division {
-                    let mut v = 0.0;
-                    for j in 0..m {
-                        let tmp = g[j] * q;
-                        gt[j] = tmp;
-                        v += (c_norm((tmp) + cache[j]) - amps[j]).abs();
-                    }
-                    if v < min_v {
-                        min_v = v;
-                        min_q = q;
-                        unsafe {
-                            std::ptr::copy_nonoverlapping(
-                                gt.as_ptr(),
-                                good_field.as_mut_ptr(),
-                                gt.len(),
-                            );
-                        }
-                    }
-                    q = q * phase_step;
+            // The sources not chosen yet when stopped do not emit
+            for &k in order[iterations..].iter() {
+                wave_sources[k].q = Complex::new(0., 0.);
+            }
+        }
+
+        let passes = if convergence == Convergence::Completed {
+            self.refine_passes
+        } else {
+            0
+        };
+        'refine: for pass in 0..passes {
+            let prev = cost;
+            for &k in order.iter() {
+                let (wave_source, g) = (&mut wave_sources[k], &transfers[k]);
+                let prev_q = wave_source.q;
+                for j in 0..l {
+                    cache[j] -= g[j] * prev_q;
+                }
+                cost = self.update(
+                    &problem,
+                    &candidates,
+                    g,
+                    &mut cache,
+                    wave_source,
+                    limits[k],
+                    Some(prev_q),
+                );
+                iterations += 1;
+                if let Some(c) = self.monitor.check(iterations, start, || cost) {
+                    convergence = c;
+                    break 'refine;
                 }
             }
+            if cost >= prev {
+                break;
+            }
+            if pass + 1 == passes {
+                convergence = Convergence::MaxIterations;
+            }
+        }
 
-            cache = cache + &good_field;
-            wave_source.q = min_q;
+        self.constraint.apply(wave_sources)?;
+
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_sources)?,
+            convergence,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Choose the emission of `wave_source` with the amplitude up to `limit` on top of `cache`, and add its field to `cache`.
+    /// The previous emission `prev` is kept if it is better than the new one.
+    /// Returns the cost after the update.
+    #[allow(clippy::too_many_arguments)]
+    fn update(
+        &self,
+        problem: &Problem,
+        candidates: &[Complex],
+        g: &[Complex],
+        cache: &mut [Complex],
+        wave_source: &mut WaveSource,
+        limit: Float,
+        prev: Option<Complex>,
+    ) -> Float {
+        let (mut q, mut v) = match self.choose(problem, candidates, g, cache, limit) {
+            Some((k, v)) => (candidates[k] * limit, v),
+            None => (Complex::new(0., 0.), Float::INFINITY),
+        };
+        if self.continuous && v.is_finite() {
+            let (rq, rv) = self.refine(problem, g, cache, q, limit);
+            if rv < v {
+                q = rq;
+                v = rv;
+            }
+        }
+        if let Some(prev) = prev {
+            let pv = problem.eval(&self.cost, g, cache, prev);
+            if pv <= v {
+                q = prev;
+                v = pv;
+            }
+        }
+        for j in 0..cache.len() {
+            cache[j] += g[j] * q;
         }
+        wave_source.q = q;
+        v
+    }
+
+    /// Search the phase and then the amplitude around `q` within half a division step, keeping the amplitude up to `limit`
+    fn refine(
+        &self,
+        problem: &Problem,
+        g: &[Complex],
+        cache: &[Complex],
+        q: Complex,
+        limit: Float,
+    ) -> (Complex, Float) {
+        let eval = |amp: Float, phase: Float| {
+            problem.eval(&self.cost, g, cache, amp * Complex::new(0., phase).exp())
+        };
+
+        let amp = q.norm();
+        let half_phase = PI / self.phase_division as Float;
+        let phase = golden_section(
+            |phase| eval(amp, phase),
+            q.arg() - half_phase,
+            q.arg() + half_phase,
+        );
+
+        let half_amp = 0.5 * limit / self.amp_division as Float;
+        let amp = golden_section(
+            |amp| eval(amp, phase),
+            (amp - half_amp).max(0.),
+            (amp + half_amp).min(limit),
+        );
+
+        (amp * Complex::new(0., phase).exp(), eval(amp, phase))
     }
 }
 
 impl Optimizer for GreedyBruteForce {
-    fn optimize(&self, wave_source: &mut [WaveSource]) {
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
         self.optimize_amp_phase(wave_source)
     }
 
-    fn set_target_foci(&mut self, foci: &[Vector3]) {
-        self.foci = foci.to_vec();
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::Calibration;
+
+    fn sources(q: Complex) -> Vec<WaveSource> {
+        (0..16)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, q)
+            })
+            .collect()
+    }
+
+    fn greedy() -> GreedyBruteForce {
+        let mut greedy = GreedyBruteForce::new(8, 4, false);
+        greedy.set_target_foci(&[[0., 0., 100.], [30., 30., 100.]]);
+        greedy.set_target_amps(&[1., 0.5]);
+        greedy
+    }
+
+    #[test]
+    fn warm_start_without_refinement_runs_greedy_pass() {
+        let mut cold = sources(Complex::new(0., 0.));
+        greedy().optimize(&mut cold).unwrap();
+
+        // The warm start has nothing to refine, so the initial emissions are discarded
+        let mut warm = sources(Complex::new(0., 1.));
+        greedy().with_warm_start(true).optimize(&mut warm).unwrap();
+        assert_eq!(
+            cold.iter().map(|s| s.q).collect::<Vec<_>>(),
+            warm.iter().map(|s| s.q).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn parallel_evaluation_is_identical_to_sequential() {
+        let run = |parallel: bool| {
+            let mut greedy = GreedyBruteForce::new(8, 4, true)
+                .with_seed(3)
+                .with_parallel(parallel)
+                .with_refinement(1)
+                .with_continuous_refinement(true);
+            greedy.set_target_foci(&[[0., 0., 100.], [30., 30., 100.]]);
+            greedy.set_target_amps(&[1., 0.5]);
+            let mut wave_source = sources(Complex::new(0., 0.));
+            greedy.optimize(&mut wave_source).unwrap();
+            wave_source.iter().map(|s| s.q).collect::<Vec<_>>()
+        };
+        assert_eq!(run(false), run(true));
+    }
+
+    #[test]
+    fn refinement_does_not_increase_cost() {
+        let mut wave_source = sources(Complex::new(0., 0.));
+        let report = greedy().optimize(&mut wave_source).unwrap();
+        let refined = greedy()
+            .with_warm_start(true)
+            .with_refinement(2)
+            .optimize(&mut wave_source)
+            .unwrap();
+        assert!(refined.cost <= report.cost + 1e-12);
+    }
+
+    #[test]
+    fn per_transducer_max_scales_the_candidates() {
+        // Halving the emissions halves the field, so the search for the halved targets makes the same choices
+        let mut half = greedy()
+            .with_amplitude_constraint(AmplitudeConstraint::PerTransducerMax(vec![0.5; 16]));
+        half.set_target_amps(&[0.5, 0.25]);
+        let mut limited = sources(Complex::new(0., 0.));
+        half.optimize(&mut limited).unwrap();
+
+        let mut full = sources(Complex::new(0., 0.));
+        greedy().optimize(&mut full).unwrap();
+        for (l, f) in limited.iter().zip(full.iter()) {
+            assert_eq!(l.q, f.q * 0.5);
+        }
+    }
+
+    #[test]
+    fn calibrated_cost_is_that_of_emission() {
+        let gains: Vec<_> = (0..16).map(|i| [0.5, 0., 1., 0.8][i % 4]).collect();
+        let calibration = Calibration::new(&[0.1; 16], &gains);
+        let mut greedy = greedy().with_refinement(2).with_continuous_refinement(true);
+        let mut wave_source = sources(Complex::new(0., 0.));
+        let report = greedy
+            .optimize_calibrated(&mut wave_source, &calibration)
+            .unwrap();
+        assert!(wave_source.iter().all(|s| s.q.norm() <= 1. + 1e-12));
+
+        calibration.emit(&mut wave_source);
+        for (s, &gain) in wave_source.iter().zip(gains.iter()) {
+            assert!(s.q.norm() <= gain + 1e-12);
+        }
+        let error =
+            amplitude_error(&Propagation::Analytic, greedy.targets(), &wave_source).unwrap();
+        assert!((report.cost - error).abs() <= 1e-9 * error.max(1.));
+    }
+
+    #[test]
+    fn penalties_are_those_of_report() {
+        let mut targets = Targets::new();
+        targets.set_foci(&[[0., 0., 100.]]);
+        targets.set_amps(&[1.]);
+        targets.set_null_points(&[[10., 0., 100.]], &[2.]);
+        targets.set_upper_bounds(&[[20., 0., 100.]], &[0.5]);
+        let problem = Problem::new(&targets);
+
+        let field = [
+            Complex::new(0.6, 0.2),
+            Complex::new(0.3, -0.4),
+            Complex::new(0.9, 0.),
+        ];
+        let zero = [Complex::new(0., 0.); 3];
+        let v = problem.eval(&Cost::L2, &zero, &field, Complex::new(0., 0.));
+        let expected = crate::optimizer::report::control_point_error(&targets, &field);
+        assert!((v - expected).abs() < 1e-12, "{} != {}", v, expected);
     }
 
-    fn set_target_amps(&mut self, amps: &[Float]) {
-        self.amps = amps.to_vec();
+    #[test]
+    fn relative_cost_rejects_zero_amplitude() {
+        let mut greedy = greedy().with_cost(Cost::Relative);
+        greedy.set_target_amps(&[1., 0.]);
+        assert!(matches!(
+            greedy.optimize(&mut sources(Complex::new(0., 0.))),
+            Err(OptimizeError::InvalidAmplitude { focus: 1, .. })
+        ));
     }
 }
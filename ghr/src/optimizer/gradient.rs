@@ -0,0 +1,507 @@
+/*
+ * File: gradient.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+use crate::{
+    math_utils::c_norm,
+    optimizer::{
+        report::amplitude_error, AmplitudeConstraint, Convergence, Monitor, OptimizeError,
+        OptimizeReport, Optimizer, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float,
+};
+
+use ndarray::*;
+use serde::{Deserialize, Serialize};
+
+/// Error at a focus whose phase is free
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// (|p| - a)^2
+    Amplitude,
+    /// (|p|^2 - a^2)^2
+    Intensity,
+}
+
+/// Update rule of the parameters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum StepRule {
+    Adam {
+        learning_rate: Float,
+        beta_1: Float,
+        beta_2: Float,
+        eps: Float,
+    },
+    /// L-BFGS with a backtracking line search
+    #[serde(rename = "lbfgs")]
+    LBFGS { memory: usize },
+}
+
+impl StepRule {
+    pub fn adam(learning_rate: Float) -> Self {
+        StepRule::Adam {
+            learning_rate,
+            beta_1: 0.9,
+            beta_2: 0.999,
+            eps: 1e-8,
+        }
+    }
+
+    pub fn lbfgs(memory: usize) -> Self {
+        StepRule::LBFGS { memory }
+    }
+}
+
+const ARMIJO_C: Float = 1e-4;
+const MAX_LINE_SEARCH_ITER: usize = 30;
+
+/// First-order optimizer with the analytic gradient of the cost
+///
+/// The cost is the weighted sum of the errors at the foci, the squared amplitudes at the null points
+/// and the squared excesses over the upper bounds. The error of a focus whose phase is specified is measured on the complex value.
+/// The parameters are the phases of the sources and, optionally, their amplitudes within `[amp_min, amp_max]`.
+/// Only the transfer matrix between the sources and the control points is stored,
+/// so that the memory and the time per iteration are linear in the number of sources.
+pub struct Gradient {
+    targets: Targets,
+    k_max: usize,
+    tol: Float,
+    objective: Objective,
+    step: StepRule,
+    amp_bounds: Option<(Float, Float)>,
+    constraint: AmplitudeConstraint,
+    propagation: Propagation,
+    monitor: Monitor,
+}
+
+impl Gradient {
+    pub fn new(k_max: usize, tol: Float) -> Self {
+        Self {
+            targets: Targets::new(),
+            k_max,
+            tol,
+            objective: Objective::Amplitude,
+            step: StepRule::lbfgs(10),
+            amp_bounds: None,
+            constraint: AmplitudeConstraint::Clamp,
+            propagation: Propagation::Analytic,
+            monitor: Monitor::new(),
+        }
+    }
+
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// See `Monitor`.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::Clamp` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    /// `StepRule::lbfgs(10)` is used by default.
+    pub fn with_step_rule(mut self, step: StepRule) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Optimize the amplitudes of the sources within `[amp_min, amp_max]` as well as the phases.
+    /// Only the phases are optimized by default, with the amplitudes of 1.
+    pub fn with_amplitude(mut self, amp_min: Float, amp_max: Float) -> Self {
+        self.amp_bounds = Some((amp_min, amp_max));
+        self
+    }
+
+    fn project(&self, x: &mut Array1<Float>, n: usize) {
+        if let Some((lo, hi)) = self.amp_bounds {
+            for a in x.slice_mut(s![n..]).iter_mut() {
+                *a = a.max(lo).min(hi);
+            }
+        }
+    }
+
+    /// Zero the components of the direction `d` which would leave the box constraints from `x`
+    fn restrict(&self, x: &Array1<Float>, d: &mut Array1<Float>, n: usize) {
+        if let Some((lo, hi)) = self.amp_bounds {
+            for j in n..x.len() {
+                if (x[j] <= lo && d[j] < 0.) || (x[j] >= hi && d[j] > 0.) {
+                    d[j] = 0.;
+                }
+            }
+        }
+    }
+
+    /// Largest component of the gradient projected onto the feasible directions
+    fn projected_grad_norm(&self, x: &Array1<Float>, g: &Array1<Float>, n: usize) -> Float {
+        let mut d = -g;
+        self.restrict(x, &mut d, n);
+        d.iter().fold(0.0, |acc: Float, d| acc.max(d.abs()))
+    }
+
+    fn emission(&self, x: &Array1<Float>, n: usize) -> Array1<Complex> {
+        Array::from_shape_fn(n, |j| {
+            let amp = if self.amp_bounds.is_some() {
+                x[n + j]
+            } else {
+                1.0
+            };
+            amp * Complex::new(0., x[j]).exp()
+        })
+    }
+
+    /// Returns the cost and its gradient with respect to the parameters.
+    /// The rows of `G` are the foci, the null points and the upper bounds in this order.
+    #[allow(non_snake_case)]
+    fn evaluate(
+        &self,
+        G: &ArrayView2<Complex>,
+        x: &Array1<Float>,
+        n: usize,
+    ) -> (Float, Array1<Float>) {
+        let m = self.targets.foci().len();
+        let m_null = m + self.targets.null_points().len();
+        let amps = self.targets.amps();
+
+        let q = self.emission(x, n);
+        let p = G.dot(&q);
+
+        // d[k] is twice the derivative of the cost with respect to the conjugate of p[k]
+        let mut f = 0.0;
+        let mut d: Array1<Complex> = Array::zeros(p.len());
+        for k in 0..m {
+            let w = self.targets.weight(k);
+            match self.targets.complex_amp(k) {
+                Some(t) => {
+                    let e = p[k] - t;
+                    f += w * e.norm_sqr();
+                    d[k] = 2.0 * w * e;
+                }
+                None => match self.objective {
+                    Objective::Amplitude => {
+                        let norm = c_norm(p[k]);
+                        let e = norm - amps[k];
+                        f += w * e * e;
+                        if norm > 0. {
+                            d[k] = 2.0 * w * e * p[k] / norm;
+                        }
+                    }
+                    Objective::Intensity => {
+                        let e = p[k].norm_sqr() - amps[k] * amps[k];
+                        f += w * e * e;
+                        d[k] = 4.0 * w * e * p[k];
+                    }
+                },
+            }
+        }
+        for (k, &w) in (m..m_null).zip(self.targets.null_weights().iter()) {
+            f += w * p[k].norm_sqr();
+            d[k] = 2.0 * w * p[k];
+        }
+        for (k, &limit) in (m_null..p.len()).zip(self.targets.bound_limits().iter()) {
+            let norm = c_norm(p[k]);
+            let e = (norm - limit).max(0.);
+            f += e * e;
+            if e > 0. {
+                d[k] = 2.0 * e * p[k] / norm;
+            }
+        }
+
+        // G^H d = conj(G^T conj(d)), without forming G^H
+        let h = G.t().dot(&d.mapv(|c| c.conj())).mapv(|c| c.conj());
+        let mut grad = Array::zeros(x.len());
+        for j in 0..n {
+            grad[j] = -(h[j].conj() * q[j]).im;
+            if self.amp_bounds.is_some() {
+                grad[n + j] = (h[j].conj() * Complex::new(0., x[j]).exp()).re;
+            }
+        }
+        (f, grad)
+    }
+
+    #[allow(non_snake_case, clippy::too_many_arguments)]
+    fn adam(
+        &self,
+        G: &ArrayView2<Complex>,
+        x: &mut Array1<Float>,
+        n: usize,
+        learning_rate: Float,
+        beta_1: Float,
+        beta_2: Float,
+        eps: Float,
+        start: Instant,
+    ) -> (usize, Float, Convergence) {
+        let mut m: Array1<Float> = Array::zeros(x.len());
+        let mut v: Array1<Float> = Array::zeros(x.len());
+        for k in 1..=self.k_max {
+            let (_, g) = self.evaluate(G, x, n);
+            if self.projected_grad_norm(x, &g, n) <= self.tol {
+                return (
+                    k - 1,
+                    self.evaluate(G, x, n).0,
+                    Convergence::GradientTolerance,
+                );
+            }
+            m = beta_1 * m + (1.0 - beta_1) * &g;
+            v = beta_2 * v + (1.0 - beta_2) * g.mapv(|g| g * g);
+            let m_hat = &m / (1.0 - beta_1.powi(k as i32));
+            let v_hat = &v / (1.0 - beta_2.powi(k as i32));
+            Zip::from(&mut *x)
+                .and(&m_hat)
+                .and(&v_hat)
+                .apply(|x, &m, &v| *x -= learning_rate * m / (v.sqrt() + eps));
+            self.project(x, n);
+            if let Some(c) = self.monitor.check(k, start, || self.evaluate(G, x, n).0) {
+                return (k, self.evaluate(G, x, n).0, c);
+            }
+        }
+        (
+            self.k_max,
+            self.evaluate(G, x, n).0,
+            Convergence::MaxIterations,
+        )
+    }
+
+    #[allow(non_snake_case)]
+    fn lbfgs(
+        &self,
+        G: &ArrayView2<Complex>,
+        x: &mut Array1<Float>,
+        n: usize,
+        memory: usize,
+        start: Instant,
+    ) -> (usize, Float, Convergence) {
+        let mut history: VecDeque<(Array1<Float>, Array1<Float>, Float)> = VecDeque::new();
+        let (mut f, mut g) = self.evaluate(G, x, n);
+        for k in 0..self.k_max {
+            if self.projected_grad_norm(x, &g, n) <= self.tol {
+                return (k, f, Convergence::GradientTolerance);
+            }
+
+            // two-loop recursion
+            let mut d = -&g;
+            let mut alphas = Vec::with_capacity(history.len());
+            for (s, y, rho) in history.iter().rev() {
+                let alpha = rho * s.dot(&d);
+                d.scaled_add(-alpha, y);
+                alphas.push(alpha);
+            }
+            if let Some((s, y, _)) = history.back() {
+                d *= s.dot(y) / y.dot(y);
+            }
+            for ((s, y, rho), alpha) in history.iter().zip(alphas.iter().rev()) {
+                let beta = rho * y.dot(&d);
+                d.scaled_add(alpha - beta, s);
+            }
+            self.restrict(x, &mut d, n);
+            if g.dot(&d) >= 0. {
+                history.clear();
+                d = -&g;
+                self.restrict(x, &mut d, n);
+            }
+
+            let mut t = 1.0;
+            let mut next = None;
+            for _ in 0..MAX_LINE_SEARCH_ITER {
+                let mut x_new = &*x + &(t * &d);
+                self.project(&mut x_new, n);
+                let (f_new, g_new) = self.evaluate(G, &x_new, n);
+                if f_new <= f + ARMIJO_C * g.dot(&(&x_new - &*x)) {
+                    next = Some((x_new, f_new, g_new));
+                    break;
+                }
+                t *= 0.5;
+            }
+            let (x_new, f_new, g_new) = match next {
+                Some(next) => next,
+                None => return (k, f, Convergence::StepTolerance),
+            };
+
+            let s = &x_new - &*x;
+            let y = &g_new - &g;
+            let sy = s.dot(&y);
+            let step = s.dot(&s).sqrt();
+            if sy > Float::EPSILON {
+                if history.len() == memory {
+                    history.pop_front();
+                }
+                history.push_back((s, y, 1.0 / sy));
+            }
+            *x = x_new;
+            f = f_new;
+            g = g_new;
+            if step <= self.tol * (x.dot(&*x).sqrt() + self.tol) {
+                return (k + 1, f, Convergence::StepTolerance);
+            }
+            if let Some(c) = self.monitor.check(k + 1, start, || f) {
+                return (k + 1, f, c);
+            }
+        }
+        (self.k_max, f, Convergence::MaxIterations)
+    }
+}
+
+impl Optimizer for Gradient {
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
+    }
+
+    #[allow(non_snake_case)]
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+
+        let n = wave_source.len();
+        let m = self.targets.foci().len();
+
+        let G = self
+            .propagation
+            .matrix(wave_source, &self.targets.control_points())?;
+        let G = G.matrix();
+
+        // Start from the back propagation of the targets
+        let mut x = Array::zeros(if self.amp_bounds.is_some() { 2 * n } else { n });
+        for j in 0..n {
+            let q0 = (0..m).fold(Complex::new(0., 0.), |acc, i| {
+                let phase = self.targets.phase(i).unwrap_or(0.);
+                acc + self.targets.amps()[i] * Complex::new(0., phase).exp() * G[[i, j]].conj()
+            });
+            x[j] = q0.arg();
+        }
+        if let Some((_, hi)) = self.amp_bounds {
+            x.slice_mut(s![n..]).fill(hi);
+        }
+
+        let (iterations, _, convergence) = match self.step {
+            StepRule::Adam {
+                learning_rate,
+                beta_1,
+                beta_2,
+                eps,
+            } => self.adam(&G, &mut x, n, learning_rate, beta_1, beta_2, eps, start),
+            StepRule::LBFGS { memory } => self.lbfgs(&G, &mut x, n, memory, start),
+        };
+
+        let q = self.emission(&x, n);
+        for j in 0..n {
+            wave_source[j].q = q[j];
+        }
+        self.constraint.apply(wave_source)?;
+
+        // The error of the emissions after the constraint rather than the objective, as in the other optimizers
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources() -> Vec<WaveSource> {
+        (0..16)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, Complex::new(0., 0.))
+            })
+            .collect()
+    }
+
+    fn gradient(k_max: usize) -> Gradient {
+        let mut gradient = Gradient::new(k_max, 1e-10);
+        gradient.set_target_foci(&[[10., 10., 100.], [20., 15., 100.]]);
+        gradient.set_target_amps(&[1., 0.5]);
+        gradient
+    }
+
+    #[test]
+    fn step_rules_lower_the_cost() {
+        let mut wave_source = sources();
+        let initial = gradient(0).optimize(&mut wave_source).unwrap().cost;
+
+        for step in &[StepRule::adam(0.05), StepRule::lbfgs(10)] {
+            let mut wave_source = sources();
+            let report = gradient(200)
+                .with_step_rule(*step)
+                .optimize(&mut wave_source)
+                .unwrap();
+            assert!(
+                report.cost < initial,
+                "{:?}: {} >= {}",
+                step,
+                report.cost,
+                initial
+            );
+        }
+    }
+
+    #[test]
+    fn amplitudes_stay_within_bounds() {
+        for step in &[StepRule::adam(0.05), StepRule::lbfgs(10)] {
+            let mut wave_source = sources();
+            gradient(200)
+                .with_step_rule(*step)
+                .with_amplitude(0.2, 0.8)
+                .optimize(&mut wave_source)
+                .unwrap();
+            for source in &wave_source {
+                let amp = source.q.norm();
+                assert!(
+                    (0.2 - 1e-9..=0.8 + 1e-9).contains(&amp),
+                    "{:?}: {}",
+                    step,
+                    amp
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * File: gs.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{sync::Arc, time::Instant};
+
+use crate::{
+    math_utils::{c_norm, c_unit},
+    optimizer::{
+        report::amplitude_error, AmplitudeConstraint, Convergence, Monitor, OptimizeError,
+        OptimizeReport, Optimizer, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float,
+};
+
+use ndarray::*;
+
+/// Gerchberg-Saxton
+///
+/// The field is propagated back and forth between the sources and the foci.
+/// The amplitudes at the foci are replaced with the targets, and the amplitudes of the sources with 1.
+pub struct GS {
+    targets: Targets,
+    repeat: usize,
+    constraint: AmplitudeConstraint,
+    propagation: Propagation,
+    monitor: Monitor,
+}
+
+/// Reference
+/// * Ralph W Gerchberg and W Owen Saxton. "A practical algorithm for the determination of phase from image and diffraction plane pictures," Optik, 35:237–246, 1972.
+impl GS {
+    pub fn new(repeat: usize) -> Self {
+        Self {
+            targets: Targets::new(),
+            repeat,
+            constraint: AmplitudeConstraint::PhaseOnly,
+            propagation: Propagation::Analytic,
+            monitor: Monitor::new(),
+        }
+    }
+
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// See `Monitor`.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::PhaseOnly` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+}
+
+impl Optimizer for GS {
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
+    }
+
+    #[allow(non_snake_case)]
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+        self.targets.check_foci_only("GS")?;
+
+        let foci = self.targets.foci();
+        let amps = self.targets.amps();
+
+        let m = foci.len();
+        let n = wave_source.len();
+
+        let G = self.propagation.matrix(wave_source, foci)?;
+        let G = G.matrix();
+        let Gh = G.t().mapv(|g: Complex| g.conj());
+
+        // The phase of the focus is kept at the target if specified
+        let phases: Vec<_> = (0..m)
+            .map(|i| {
+                self.targets
+                    .phase(i)
+                    .map(|phase| Complex::new(0., phase).exp())
+            })
+            .collect();
+
+        // Squared amplitude error at the foci, which is passed to the monitor
+        let error = |q: &Array1<Complex>| -> Float {
+            let p = G.dot(q);
+            (0..m)
+                .map(|i| {
+                    let e = c_norm(p[i]) - amps[i];
+                    self.targets.weight(i) * e * e
+                })
+                .sum()
+        };
+
+        let mut q: Array1<Complex> = Array::from_elem(n, Complex::new(1., 0.));
+        let mut iterations = self.repeat;
+        let mut convergence = Convergence::Completed;
+        for k in 0..self.repeat {
+            let mut p = G.dot(&q);
+            for i in 0..m {
+                let direction = phases[i].unwrap_or_else(|| c_unit(p[i]));
+                p[i] = self.targets.weight(i) * amps[i] * direction;
+            }
+            q = Gh.dot(&p);
+            q.mapv_inplace(c_unit);
+            if let Some(c) = self.monitor.check(k + 1, start, || error(&q)) {
+                iterations = k + 1;
+                convergence = c;
+                break;
+            }
+        }
+
+        for j in 0..n {
+            wave_source[j].q = q[j];
+        }
+        self.constraint.apply(wave_source)?;
+
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer_matrix::Propagation;
+
+    #[test]
+    fn unreachable_focus_gives_finite_emissions() {
+        // The second focus is not reached by any source
+        let mut matrix = Array::zeros((2, 4));
+        matrix.row_mut(0).fill(Complex::new(1., 0.));
+        let mut gs = GS::new(10).with_propagation(Propagation::imported(matrix));
+        gs.set_target_foci(&[[0., 0., 100.], [0., 0., 200.]]);
+        gs.set_target_amps(&[1., 1.]);
+
+        let mut wave_source: Vec<_> = (0..4)
+            .map(|i| WaveSource::new([i as Float * 10., 0., 0.], Complex::new(0., 0.)))
+            .collect();
+        gs.optimize(&mut wave_source).unwrap();
+        assert!(wave_source
+            .iter()
+            .all(|s| s.q.re.is_finite() && s.q.im.is_finite()));
+    }
+}
@@ -11,18 +11,32 @@
  *
  */
 
+use std::{sync::Arc, time::Instant};
+
 use crate::{
-    float::Float, math_utils::c_norm, optimizer::Optimizer, utils::transfer,
-    wave_source::WaveSource, Complex, Vector3,
+    math_utils::{c_norm, c_unit},
+    optimizer::{
+        report::amplitude_error,
+        sequence::{check_previous, Temporal},
+        AmplitudeConstraint, Convergence, History, Monitor, OptimizeError, OptimizeReport,
+        Optimizer, Record, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float,
 };
 
 use ndarray::*;
 
 /// GS-PAT
 pub struct GSPAT {
-    foci: Vec<Vector3>,
-    amps: Vec<Float>,
+    targets: Targets,
     repeat: usize,
+    constraint: AmplitudeConstraint,
+    warm_start: bool,
+    propagation: Propagation,
+    monitor: Monitor,
+    history: Option<History>,
 }
 
 /// Reference
@@ -32,85 +46,312 @@ pub struct GSPAT {
 impl GSPAT {
     pub fn new(repeat: usize) -> Self {
         Self {
-            foci: vec![],
-            amps: vec![],
+            targets: Targets::new(),
             repeat,
+            constraint: AmplitudeConstraint::Clamp,
+            warm_start: false,
+            propagation: Propagation::Analytic,
+            monitor: Monitor::new(),
+            history: None,
         }
     }
-}
 
-impl Optimizer for GSPAT {
-    fn set_target_foci(&mut self, foci: &[Vector3]) {
-        self.foci = foci.to_vec();
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// See `Monitor`.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Record the weighted squared amplitude error at the foci and the norm of the update of the target field at each iteration into `history`.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Start from the phases of the field produced by the current emissions of the wave sources at the foci.
+    pub fn with_warm_start(mut self, warm_start: bool) -> Self {
+        self.warm_start = warm_start;
+        self
     }
 
-    fn set_target_amps(&mut self, amps: &[Float]) {
-        self.amps = amps.to_vec();
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::Clamp` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
     }
+}
 
+impl GSPAT {
+    /// `prior` is the emissions of the previous frame and the weight of the temporal regularization
     #[allow(non_snake_case, clippy::many_single_char_names)]
-    fn optimize(&self, wave_source: &mut [WaveSource]) {
+    fn optimize_with(
+        &self,
+        wave_source: &mut [WaveSource],
+        prior: Option<(&[Complex], Float)>,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+        self.targets.check_foci_only("GSPAT")?;
+        if let Some(history) = &self.history {
+            history.clear();
+        }
+
         let num_trans = wave_source.len();
-        let foci = &self.foci;
-        let amps = &self.amps;
+        let foci = self.targets.foci();
+        let amps = self.targets.amps();
 
         let m = foci.len();
         let n = num_trans;
 
-        let mut G = Array::zeros((m, n));
-        for i in 0..m {
-            let fp = foci[i];
-            for j in 0..n {
-                G[[i, j]] = transfer(wave_source[j].pos, fp);
-            }
-        }
+        let G = self.propagation.matrix(wave_source, foci)?;
+        let G = G.matrix();
 
+        // The weights scale the back propagation so that heavier foci dominate the phase retrieval.
+        // Their effect on the amplitudes is canceled by the final correction.
         let mut B = Array::zeros((n, m));
         for i in 0..m {
             let mut denomi = 0.0;
             for j in 0..n {
                 denomi += G[[i, j]].norm_sqr();
             }
+            // A focus not reached by any source is left out of the back propagation
+            if denomi == 0.0 {
+                continue;
+            }
+            let w = self.targets.weight(i);
             for j in 0..n {
-                B[[j, i]] = Complex::new(amps[i], 0.0) * G[[i, j]].conj() / denomi;
+                B[[j, i]] = Complex::new(w * amps[i], 0.0) * G[[i, j]].conj() / denomi;
             }
         }
 
         let R = G.dot(&B);
 
+        // The emissions B p are blended with the previous frame at each iteration,
+        // i.e., the proximal step of |q' - q|^2 + weight * |q' - previous|^2, so that the phase retrieval sees the blended field.
+        let prior = prior.map(|(previous, weight)| {
+            let previous = Array1::from(previous.to_vec());
+            (G.dot(&previous), previous, weight)
+        });
+        let propagate = |p: &Array1<Complex>| match &prior {
+            Some((field, _, weight)) => {
+                let mut gamma = R.dot(p);
+                gamma.zip_mut_with(field, |g, &f| *g = (*g + weight * f) / (1.0 + weight));
+                gamma
+            }
+            None => R.dot(p),
+        };
+
+        // The phase of the focus is kept at the target if specified
+        let phases: Vec<_> = (0..m)
+            .map(|i| {
+                self.targets
+                    .phase(i)
+                    .map(|phase| Complex::new(0., phase).exp())
+            })
+            .collect();
+        let direction = |i: usize, g: Complex| match phases[i] {
+            Some(d) => d,
+            None => c_unit(g),
+        };
+
         let mut p0: ArrayBase<OwnedRepr<Complex>, _> = Array::zeros(m);
         for i in 0..m {
             p0[i] = Complex::new(amps[i], 0.);
         }
-        let mut p = p0.clone();
-        let mut gamma = R.dot(&p);
-
-        for _ in 0..self.repeat {
-            for (i, v) in gamma
-                .iter()
-                .zip(p0.iter())
-                .map(|(&g, &p)| g / c_norm(g) * p)
-                .enumerate()
-            {
-                p[i] = v;
+        let field = if self.warm_start || prior.is_some() {
+            Some(self.propagation.field(wave_source, foci)?)
+        } else {
+            None
+        };
+        let initial_direction = |i: usize| match &field {
+            Some(field) => c_unit(field[i]),
+            None => Complex::new(1., 0.),
+        };
+        let mut p: ArrayBase<OwnedRepr<Complex>, _> = Array::zeros(m);
+        for i in 0..m {
+            p[i] = phases[i].unwrap_or_else(|| initial_direction(i)) * p0[i];
+        }
+        let mut gamma = propagate(&p);
+
+        let mut iterations = self.repeat;
+        let mut convergence = Convergence::Completed;
+        for k in 0..self.repeat {
+            let prev = self.history.as_ref().map(|_| p.clone());
+            for i in 0..m {
+                p[i] = direction(i, gamma[i]) * p0[i];
+            }
+            gamma = propagate(&p);
+            // gamma is the field at the foci before the amplitude correction
+            let error = || {
+                (0..m)
+                    .map(|i| {
+                        let e = c_norm(gamma[i]) - amps[i];
+                        self.targets.weight(i) * e * e
+                    })
+                    .sum()
+            };
+            if let (Some(history), Some(prev)) = (&self.history, prev) {
+                let mut record = Record::new(k + 1, error(), start);
+                record.step_norm = Some(
+                    (&p - &prev)
+                        .iter()
+                        .map(|c| c.norm_sqr())
+                        .sum::<Float>()
+                        .sqrt(),
+                );
+                history.push(record);
+            }
+            if let Some(c) = self.monitor.check(k + 1, start, error) {
+                iterations = k + 1;
+                convergence = c;
+                break;
             }
-            gamma = R.dot(&p);
         }
 
-        for (i, v) in gamma
-            .iter()
-            .zip(p0.iter())
-            .map(|(g, &p)| g / (g.norm_sqr()) * p * p)
-            .enumerate()
-        {
-            p[i] = v;
+        for i in 0..m {
+            let amp = c_norm(gamma[i]);
+            p[i] = if amp > 0. {
+                direction(i, gamma[i]) / amp * p0[i] * p0[i]
+            } else {
+                Complex::new(0., 0.)
+            };
         }
 
-        let q = B.dot(&p);
+        let mut q = B.dot(&p);
+        if let Some((_, previous, weight)) = &prior {
+            q.zip_mut_with(previous, |q, &r| *q = (*q + weight * r) / (1.0 + weight));
+        }
 
         for j in 0..n {
-            let amp = c_norm(q[j]).min(1.0);
-            wave_source[j].q = q[j] / c_norm(q[j]) * amp;
+            wave_source[j].q = q[j];
         }
+        self.constraint.apply(wave_source)?;
+
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+impl Optimizer for GSPAT {
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
+    }
+
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        self.optimize_with(wave_source, None)
+    }
+}
+
+/// The phases at the foci start from those of the previous frame,
+/// and the emissions are blended with the previous frame as the proximal step of the regularization
+/// at each iteration of the phase retrieval as well as in the result.
+impl Temporal for GSPAT {
+    fn optimize_regularized(
+        &self,
+        wave_source: &mut [WaveSource],
+        previous: &[Complex],
+        weight: Float,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        check_previous(wave_source, previous)?;
+        for (source, &q) in wave_source.iter_mut().zip(previous.iter()) {
+            source.q = q;
+        }
+        self.optimize_with(wave_source, Some((previous, weight)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer_matrix::Propagation;
+
+    fn distance(wave_source: &[WaveSource], previous: &[Complex]) -> Float {
+        wave_source
+            .iter()
+            .zip(previous.iter())
+            .map(|(s, p)| (s.q - p).norm_sqr())
+            .sum()
+    }
+
+    #[test]
+    fn regularization_pulls_toward_previous() {
+        // Unit transfer functions, so that the emissions are not clamped by the constraint
+        let matrix = Array::from_shape_fn((4, 16), |(i, j)| {
+            Complex::new(0., 0.7 * ((i + 1) * j) as Float).exp()
+        });
+        let mut wave_source: Vec<_> = (0..16)
+            .map(|i| WaveSource::new([i as Float * 10., 0., 0.], Complex::new(0., 0.)))
+            .collect();
+        let mut gspat = GSPAT::new(20).with_propagation(Propagation::imported(matrix));
+        gspat.set_target_foci(&[[0., 0., 100.], [10., 0., 100.]]);
+        gspat.set_target_amps(&[1., 1.]);
+        gspat.optimize(&mut wave_source).unwrap();
+        let previous: Vec<_> = wave_source.iter().map(|s| s.q).collect();
+
+        // The rows of the imported matrix are the control points, so the next frame changes the amplitudes
+        gspat.set_target_amps(&[0.2, 1.]);
+        let distances: Vec<_> = [0., 1., 100.]
+            .iter()
+            .map(|&weight| {
+                gspat
+                    .optimize_regularized(&mut wave_source, &previous, weight)
+                    .unwrap();
+                distance(&wave_source, &previous)
+            })
+            .collect();
+        assert!(distances[0] > distances[1], "{:?}", distances);
+        assert!(distances[1] > distances[2], "{:?}", distances);
+        assert!(distances[2] < 1e-2 * distances[0], "{:?}", distances);
+
+        assert!(matches!(
+            gspat.optimize_regularized(&mut wave_source, &previous[1..], 1.),
+            Err(OptimizeError::SourceSizeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn unreachable_focus_gives_finite_emissions() {
+        // The second focus is not reached by any source, and the last source reaches no focus
+        let mut matrix = Array::zeros((2, 4));
+        matrix.slice_mut(s![0, ..3]).fill(Complex::new(1., 0.));
+        let mut gspat = GSPAT::new(10).with_propagation(Propagation::imported(matrix));
+        gspat.set_target_foci(&[[0., 0., 100.], [0., 0., 200.]]);
+        gspat.set_target_amps(&[1., 1.]);
+
+        let mut wave_source: Vec<_> = (0..4)
+            .map(|i| WaveSource::new([i as Float * 10., 0., 0.], Complex::new(0., 0.)))
+            .collect();
+        gspat.optimize(&mut wave_source).unwrap();
+        assert!(wave_source
+            .iter()
+            .all(|s| s.q.re.is_finite() && s.q.im.is_finite()));
     }
 }
@@ -0,0 +1,332 @@
+/*
+ * File: gs_pat_batch.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    math_utils::{c_norm, c_unit},
+    optimizer::{
+        report::control_point_error, AmplitudeConstraint, Convergence, Frame, OptimizeError,
+        OptimizeReport, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache, TransferMatrix},
+    wave_source::WaveSource,
+    Complex, Float, Vector3,
+};
+
+use ndarray::*;
+use rayon::prelude::*;
+
+/// Number of propagators kept by default
+const DEFAULT_CAPACITY: usize = 64;
+
+/// Propagators of GS-PAT for a set of control points
+///
+/// The amplitudes and the weights of the foci are factored out of the propagators,
+/// i.e., `B = B0 diag(s)` and `R = R0 diag(s)` with `s = weight * amp`,
+/// so that the propagators only depend on the positions.
+#[allow(non_snake_case)]
+struct Propagator {
+    points: Vec<Vector3>,
+    /// Transfer matrix from the sources to the control points
+    G: Arc<TransferMatrix>,
+    /// Normalized back propagation to the foci
+    B0: Array2<Complex>,
+    /// `G B0` restricted to the foci
+    R0: Array2<Complex>,
+}
+
+impl Propagator {
+    #[allow(non_snake_case)]
+    fn new(
+        propagation: &Propagation,
+        wave_source: &[WaveSource],
+        points: Vec<Vector3>,
+        m: usize,
+    ) -> Result<Self, OptimizeError> {
+        let G = propagation.matrix(wave_source, &points)?;
+        let g = G.matrix();
+        let mut B0 = g.slice(s![0..m, ..]).t().mapv(|g| g.conj());
+        for (i, mut col) in B0.gencolumns_mut().into_iter().enumerate() {
+            let denomi: Float = g.row(i).iter().map(|g| g.norm_sqr()).sum();
+            // A focus not reached by any source is left out of the back propagation, as in `GSPAT`
+            if denomi == 0.0 {
+                continue;
+            }
+            col.mapv_inplace(|b| b / denomi);
+        }
+        let R0 = g.slice(s![0..m, ..]).dot(&B0);
+        Ok(Self { points, G, B0, R0 })
+    }
+}
+
+/// Result of a batch
+#[derive(Debug, Clone)]
+pub struct BatchReport {
+    pub frames: Vec<Frame>,
+    /// Number of propagators built in the batch, i.e., the number of the focus sets not seen before
+    pub propagators_built: usize,
+    /// Wall-clock time taken by the whole batch
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// Frames per second of the batch
+    pub fn fps(&self) -> Float {
+        self.frames.len() as Float / self.elapsed.as_secs_f64()
+    }
+}
+
+/// GS-PAT for many frames on a fixed array
+///
+/// The propagators are kept for each set of control points, so that they are built only once
+/// as long as the foci are unchanged, and the frames are solved in parallel.
+/// The amplitudes, the phases and the weights of the targets may change freely between the frames.
+/// As well as `GSPAT`, only the foci are taken into account, and the frames with null points or upper bounds are rejected.
+pub struct GSPATBatch {
+    wave_source: Vec<WaveSource>,
+    repeat: usize,
+    constraint: AmplitudeConstraint,
+    capacity: usize,
+    propagators: VecDeque<Arc<Propagator>>,
+    propagation: Propagation,
+}
+
+impl GSPATBatch {
+    /// Only the positions of `wave_source` are used.
+    pub fn new(wave_source: &[WaveSource], repeat: usize) -> Self {
+        Self {
+            wave_source: wave_source.to_vec(),
+            repeat,
+            constraint: AmplitudeConstraint::Clamp,
+            capacity: DEFAULT_CAPACITY,
+            propagators: VecDeque::new(),
+            propagation: Propagation::Analytic,
+        }
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::Clamp` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Number of propagators kept across the batches. The least recently used one is dropped when full.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    /// The kept propagators are dropped.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self.propagators.clear();
+        self
+    }
+
+    pub fn wave_source(&self) -> &[WaveSource] {
+        &self.wave_source
+    }
+
+    /// Drop the kept propagators.
+    pub fn clear(&mut self) {
+        self.propagators.clear();
+    }
+
+    /// Optimize all the frames. The frames are independent of each other.
+    pub fn optimize(&mut self, frames: &[Targets]) -> Result<BatchReport, OptimizeError> {
+        let start = Instant::now();
+        for targets in frames {
+            targets.check(&self.wave_source, 1)?;
+            targets.check_foci_only("GSPATBatch")?;
+        }
+
+        let mut propagators_built = 0;
+        let mut propagators = Vec::with_capacity(frames.len());
+        for targets in frames {
+            let points = targets.control_points();
+            let kept = self
+                .propagators
+                .iter()
+                .position(|p| p.points == points)
+                .and_then(|i| self.propagators.remove(i));
+            let propagator = match kept {
+                Some(propagator) => propagator,
+                None => {
+                    propagators_built += 1;
+                    Arc::new(Propagator::new(
+                        &self.propagation,
+                        &self.wave_source,
+                        points,
+                        targets.foci().len(),
+                    )?)
+                }
+            };
+            // The most recently used one is kept at the front
+            if self.capacity > 0 {
+                if self.propagators.len() == self.capacity {
+                    self.propagators.pop_back();
+                }
+                self.propagators.push_front(propagator.clone());
+            }
+            propagators.push(propagator);
+        }
+
+        let frames = frames
+            .par_iter()
+            .zip(propagators.par_iter())
+            .map(|(targets, propagator)| self.solve(targets, propagator))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(BatchReport {
+            frames,
+            propagators_built,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    #[allow(non_snake_case)]
+    fn solve(&self, targets: &Targets, propagator: &Propagator) -> Result<Frame, OptimizeError> {
+        let start = Instant::now();
+        let amps = targets.amps();
+        let m = amps.len();
+        let s: Array1<Complex> = (0..m)
+            .map(|i| Complex::new(targets.weight(i) * amps[i], 0.))
+            .collect();
+
+        // The phase of the focus is kept at the target if specified
+        let phases: Vec<_> = (0..m)
+            .map(|i| targets.phase(i).map(|phase| Complex::new(0., phase).exp()))
+            .collect();
+        let direction = |i: usize, g: Complex| match phases[i] {
+            Some(d) => d,
+            None => c_unit(g),
+        };
+
+        let mut p: Array1<Complex> = (0..m)
+            .map(|i| phases[i].unwrap_or_else(|| Complex::new(1., 0.)) * amps[i])
+            .collect();
+        let mut gamma = propagator.R0.dot(&(&s * &p));
+        for _ in 0..self.repeat {
+            for i in 0..m {
+                p[i] = direction(i, gamma[i]) * amps[i];
+            }
+            gamma = propagator.R0.dot(&(&s * &p));
+        }
+        for i in 0..m {
+            let amp = c_norm(gamma[i]);
+            p[i] = if amp > 0. {
+                direction(i, gamma[i]) / amp * amps[i] * amps[i]
+            } else {
+                Complex::new(0., 0.)
+            };
+        }
+        let q = propagator.B0.dot(&(&s * &p));
+
+        let mut wave_source = self.wave_source.clone();
+        for (source, &q) in wave_source.iter_mut().zip(q.iter()) {
+            source.q = q;
+        }
+        self.constraint.apply(&mut wave_source)?;
+        let emissions: Array1<Complex> = wave_source.iter().map(|s| s.q).collect();
+        let field = propagator.G.matrix().dot(&emissions);
+
+        Ok(Frame {
+            emissions: emissions.to_vec(),
+            report: OptimizeReport {
+                iterations: self.repeat,
+                cost: control_point_error(targets, field.as_slice().unwrap()),
+                convergence: Convergence::Completed,
+                elapsed: start.elapsed(),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{Optimizer, GSPAT};
+
+    fn sources() -> Vec<WaveSource> {
+        (0..16)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, Complex::new(0., 0.))
+            })
+            .collect()
+    }
+
+    fn frame(foci: &[Vector3], amps: &[Float]) -> Targets {
+        let mut targets = Targets::new();
+        targets.set_foci(foci);
+        targets.set_amps(amps);
+        targets
+    }
+
+    #[test]
+    fn frames_are_those_of_gspat() {
+        let mut weighted = frame(&[[10., 10., 100.], [20., 15., 100.]], &[0.02, 0.01]);
+        weighted.set_weights(&[1., 2.]);
+        let mut phased = frame(&[[15., 15., 120.], [0., 30., 100.]], &[0.01, 0.02]);
+        phased.set_phases(&[Some(1.), None]);
+        let frames = vec![weighted, phased];
+
+        let mut batch = GSPATBatch::new(&sources(), 20);
+        let report = batch.optimize(&frames).unwrap();
+        for (targets, frame) in frames.iter().zip(report.frames.iter()) {
+            let mut gspat = GSPAT::new(20);
+            *gspat.targets_mut() = targets.clone();
+            let mut wave_source = sources();
+            let cost = gspat.optimize(&mut wave_source).unwrap().cost;
+
+            assert!((frame.report.cost - cost).abs() <= 1e-9 * cost);
+            for (s, &q) in wave_source.iter().zip(frame.emissions.iter()) {
+                assert!((s.q - q).norm() <= 1e-9 * s.q.norm());
+            }
+        }
+    }
+
+    #[test]
+    fn propagators_are_reused_when_only_amplitudes_change() {
+        let foci = [[10., 10., 100.], [20., 15., 100.]];
+        let mut batch = GSPATBatch::new(&sources(), 10);
+
+        let report = batch
+            .optimize(&[frame(&foci, &[0.02, 0.01]), frame(&foci, &[0.01, 0.02])])
+            .unwrap();
+        assert_eq!(report.propagators_built, 1);
+
+        let report = batch.optimize(&[frame(&foci, &[0.015, 0.015])]).unwrap();
+        assert_eq!(report.propagators_built, 0);
+
+        let moved = [[10., 10., 100.], [20., 20., 100.]];
+        let report = batch.optimize(&[frame(&moved, &[0.02, 0.01])]).unwrap();
+        assert_eq!(report.propagators_built, 1);
+
+        batch.clear();
+        let report = batch.optimize(&[frame(&foci, &[0.02, 0.01])]).unwrap();
+        assert_eq!(report.propagators_built, 1);
+    }
+}
@@ -0,0 +1,172 @@
+/*
+ * File: history.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::Float;
+
+/// State of an optimizer after an iteration
+///
+/// The fields which the optimizer does not have are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Record {
+    pub iteration: usize,
+    /// Value of the cost function of the optimizer
+    pub cost: Float,
+    /// Damping parameter of `LM`
+    pub mu: Option<Float>,
+    /// Norm of the update of the parameters
+    pub step_norm: Option<Float>,
+    /// Ratio of the actual to the predicted decrease of the cost in `LM`
+    pub rho: Option<Float>,
+    /// Whether the step was accepted in `LM`
+    pub accepted: Option<bool>,
+    /// Wall-clock time since the optimization started in seconds
+    pub elapsed: Float,
+}
+
+impl Record {
+    pub(crate) fn new(iteration: usize, cost: Float, start: Instant) -> Self {
+        Self {
+            iteration,
+            cost,
+            mu: None,
+            step_norm: None,
+            rho: None,
+            accepted: None,
+            elapsed: start.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+/// Per-iteration records of the last optimization
+///
+/// Clones share the same records, so that the history can be read after passing it to an optimizer.
+/// The records are cleared at the beginning of each optimization.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    records: Arc<Mutex<Vec<Record>>>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn records(&self) -> Vec<Record> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.lock().unwrap().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+
+    pub(crate) fn push(&self, record: Record) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    /// Write the records in CSV with a header. Missing values are left empty.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        fn opt<T: ToString>(v: Option<T>) -> String {
+            v.map_or_else(String::new, |v| v.to_string())
+        }
+        writeln!(writer, "iteration,cost,mu,step_norm,rho,accepted,elapsed")?;
+        for r in self.records.lock().unwrap().iter() {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{}",
+                r.iteration,
+                r.cost,
+                opt(r.mu),
+                opt(r.step_norm),
+                opt(r.rho),
+                opt(r.accepted),
+                r.elapsed
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the records as a JSON array. Missing values are `null`.
+    pub fn write_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer(writer, &*self.records.lock().unwrap()).map_err(io::Error::from)
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut buf = vec![];
+        self.write_csv(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&*self.records.lock().unwrap()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        let history = History::new();
+        let start = Instant::now();
+        history.push(Record::new(0, 2., start));
+        let mut record = Record::new(1, 1., start);
+        record.mu = Some(0.5);
+        record.accepted = Some(true);
+        history.push(record);
+        history
+    }
+
+    #[test]
+    fn clones_share_records() {
+        let history = history();
+        let clone = history.clone();
+        assert_eq!(clone.len(), 2);
+        clone.clear();
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn csv_leaves_missing_values_empty() {
+        let csv = history().to_csv();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "iteration,cost,mu,step_norm,rho,accepted,elapsed");
+        assert!(lines[1].starts_with("0,2,,,,,"));
+        assert!(lines[2].starts_with("1,1,0.5,,,true,"));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn json_has_null_for_missing_values() {
+        let json: serde_json::Value = serde_json::from_str(&history().to_json()).unwrap();
+        assert_eq!(json[0]["mu"], serde_json::Value::Null);
+        assert_eq!(json[1]["mu"], 0.5);
+        assert_eq!(json[1]["accepted"], true);
+        assert_eq!(json[1]["iteration"], 1);
+    }
+}
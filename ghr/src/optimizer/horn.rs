@@ -11,8 +11,16 @@
  *
  */
 
+use std::{sync::Arc, time::Instant};
+
 use crate::{
-    optimizer::Optimizer, utils::transfer, wave_source::WaveSource, Complex, Float, Vector3,
+    optimizer::{
+        report::amplitude_error, AmplitudeConstraint, Convergence, History, Monitor, OptimizeError,
+        OptimizeReport, Optimizer, Record, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float,
 };
 
 use rand::{thread_rng, Rng};
@@ -20,24 +28,71 @@ use rand::{thread_rng, Rng};
 use ndarray::*;
 use ndarray_linalg::*;
 
+/// Semidefinite relaxation of the phase retrieval.
+/// Only the amplitudes of the foci are taken into account: null points, upper bounds, weights other than 1 and fixed phases are rejected.
 pub struct Horn {
-    foci: Vec<Vector3>,
-    amps: Vec<Float>,
+    targets: Targets,
     repeat: usize,
     alpha: Float,
     lambda: Float,
+    constraint: AmplitudeConstraint,
+    warm_start: bool,
+    propagation: Propagation,
+    monitor: Monitor,
+    history: Option<History>,
 }
 
 impl Horn {
     pub fn new(repeat: usize, alpha: Float, lambda: Float) -> Self {
         Self {
-            foci: vec![],
-            amps: vec![],
+            targets: Targets::new(),
             repeat,
             alpha,
             lambda,
+            constraint: AmplitudeConstraint::NormalizeToMax,
+            warm_start: false,
+            propagation: Propagation::Analytic,
+            monitor: Monitor::new(),
+            history: None,
         }
     }
+
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// See `Monitor`.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Record the cost tr(MX) and the norm of the update of X at each iteration into `history`.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Start the block coordinate descent from the phases of the field produced by the current emissions of the wave sources at the foci,
+    /// instead of the identity matrix.
+    pub fn with_warm_start(mut self, warm_start: bool) -> Self {
+        self.warm_start = warm_start;
+        self
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::NormalizeToMax` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
 }
 
 impl Horn {
@@ -68,41 +123,53 @@ impl Horn {
         dst.slice_mut(s![i..row, 0..i])
             .assign(&m.slice(s![(i + 1)..(row + 1), 0..i]));
         dst.slice_mut(s![0..i, i..col])
-            .assign(&m.slice(s![0..i, (i + 1)..(col as isize + 1)]));
+            .assign(&m.slice(s![0..i, (i + 1)..(col + 1)]));
         dst.slice_mut(s![i..row, i..col])
-            .assign(&m.slice(s![(i + 1)..(row + 1), (i + 1)..(col as isize + 1)]));
+            .assign(&m.slice(s![(i + 1)..(row + 1), (i + 1)..(col + 1)]));
     }
 }
 impl Optimizer for Horn {
-    fn set_target_foci(&mut self, foci: &[Vector3]) {
-        self.foci = foci.to_vec();
+    fn targets(&self) -> &Targets {
+        &self.targets
     }
 
-    fn set_target_amps(&mut self, amps: &[Float]) {
-        self.amps = amps.to_vec();
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
     }
 
     #[allow(clippy::many_single_char_names)]
-    fn optimize(&self, wave_source: &mut [WaveSource]) {
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 2)?;
+        self.targets.check_amps_only("Horn")?;
+        if let Some(history) = &self.history {
+            history.clear();
+        }
+
         let mut rng = thread_rng();
         let num_trans = wave_source.len();
-        let foci = &self.foci;
-        let amps = &self.amps;
+        let foci = self.targets.foci();
+        let amps = self.targets.amps();
 
         let alpha = self.alpha;
         let m = foci.len();
         let n = num_trans;
-        let mut b = Array::zeros((m, n));
+        let b = self.propagation.matrix(wave_source, foci)?;
+        let b = b.matrix();
         let mut p = Array::zeros((m, m));
         for i in 0..m {
             p[[i, i]] = Complex::new(amps[i], 0.);
-            let tp = foci[i];
-            for j in 0..n {
-                b[[i, j]] = transfer(wave_source[j].pos, tp);
-            }
         }
 
-        let (u, s, vt) = b.svd(true, true).unwrap();
+        let (u, s, vt) = b.svd(true, true)?;
         let mut singular_values_inv_mat = Array::zeros((n, m));
         for i in 0..m.min(n) {
             let r = s[i] / (s[i] * s[i] + alpha);
@@ -118,14 +185,40 @@ impl Optimizer for Horn {
         let mut mmc = Array::zeros(m - 1);
 
         let mut x = Array::eye(m);
+        if self.warm_start {
+            let u: Vec<_> = self
+                .propagation
+                .field(wave_source, foci)?
+                .into_iter()
+                .map(|f| {
+                    let norm = f.norm();
+                    if norm > 0. {
+                        f / norm
+                    } else {
+                        Complex::new(1., 0.)
+                    }
+                })
+                .collect();
+            for r in 0..m {
+                for c in 0..m {
+                    x[[r, c]] = u[r] * u[c].conj();
+                }
+            }
+        }
         let mut xc = Array::zeros((m - 1, m - 1));
         let zero = Array::zeros(m);
-        for _ in 0..self.repeat {
+        let mut iterations = self.repeat;
+        let mut convergence = Convergence::Completed;
+        for k in 0..self.repeat {
             let ii = rng.gen_range(0..m) as isize;
             Self::remove_row_col(&mut xc, &x, ii);
             Self::remove_row_1d(&mut mmc, &mm.column(ii as usize), ii);
             let xb = xc.dot(&mmc).into_shape((mmc.len(), 1)).unwrap();
             let gamma = Self::adjoint(&xb).dot(&mmc)[0];
+            let prev = self
+                .history
+                .as_ref()
+                .map(|_| x.column(ii as usize).to_owned());
             if gamma.re > 0.0 {
                 let xb = xb * (-(self.lambda / gamma.re).sqrt());
                 x.slice_mut(s![ii, 0..ii])
@@ -143,9 +236,20 @@ impl Optimizer for Horn {
                 x.slice_mut(s![(ii + 1).., ii])
                     .assign(&zero.slice(s![(ii + 1)..]));
             }
+            // The cost is the objective of the relaxed problem, tr(MX)
+            if let (Some(history), Some(prev)) = (&self.history, prev) {
+                let mut record = Record::new(k + 1, (&mm * &x.t()).sum().re, start);
+                record.step_norm = Some((&x.column(ii as usize) - &prev).norm_l2());
+                history.push(record);
+            }
+            if let Some(c) = self.monitor.check(k + 1, start, || (&mm * &x.t()).sum().re) {
+                iterations = k + 1;
+                convergence = c;
+                break;
+            }
         }
 
-        let (evs, vecs) = x.eig().unwrap();
+        let (evs, vecs) = x.eig()?;
         let mut abs_eiv = 0.;
         let mut idx = 0;
         for j in 0..evs.len() {
@@ -159,12 +263,16 @@ impl Optimizer for Horn {
         let u = vecs.column(idx);
         let q = pinv_b.dot(&p).dot(&u);
 
-        let max_coef = q
-            .iter()
-            .fold(Float::NEG_INFINITY, |acc, x| acc.max(x.norm_sqr()))
-            .sqrt();
         for j in 0..n {
-            wave_source[j].q = q[j] / max_coef;
+            wave_source[j].q = q[j];
         }
+        self.constraint.apply(wave_source)?;
+
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence,
+            elapsed: start.elapsed(),
+        })
     }
 }
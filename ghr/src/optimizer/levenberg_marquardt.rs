@@ -11,57 +11,203 @@
  *
  */
 
+use std::{sync::Arc, time::Instant};
+
 use crate::{
-    optimizer::Optimizer, utils::transfer, wave_source::WaveSource, Complex, Float, Vector3, PI,
+    math_utils::c_norm,
+    optimizer::{
+        report::amplitude_error,
+        sequence::{check_previous, Temporal},
+        target::MAX_ACTIVE_SET_ITER,
+        AmplitudeConstraint, Convergence, History, Monitor, OptimizeError, OptimizeReport,
+        Optimizer, Record, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float, PI,
 };
 
 use ndarray::{linalg::*, *};
 use ndarray_linalg::*;
 
 pub struct LM {
-    foci: Vec<Vector3>,
-    amps: Vec<Float>,
+    targets: Targets,
     eps_1: Float,
     eps_2: Float,
     tau: Float,
     k_max: usize,
+    matrix_free: Option<(usize, Float)>,
+    optimize_amplitude: bool,
+    constraint: AmplitudeConstraint,
+    warm_start: bool,
+    propagation: Propagation,
+    monitor: Monitor,
+    history: Option<History>,
 }
 
 impl LM {
     pub fn new(eps_1: Float, eps_2: Float, tau: Float, k_max: usize) -> Self {
         Self {
-            foci: vec![],
-            amps: vec![],
+            targets: Targets::new(),
             eps_1,
             eps_2,
             tau,
             k_max,
+            matrix_free: None,
+            optimize_amplitude: false,
+            constraint: AmplitudeConstraint::Clamp,
+            warm_start: false,
+            propagation: Propagation::Analytic,
+            monitor: Monitor::new(),
+            history: None,
         }
     }
 
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// See `Monitor`.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Record the cost, the damping `mu`, the norm of the step and the gain ratio `rho` of each iteration into `history`.
+    /// The cost is that of the current parameters, which is not updated by a rejected step.
+    pub fn with_history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Start from the current emissions of the wave sources instead of random phases.
+    pub fn with_warm_start(mut self, warm_start: bool) -> Self {
+        self.warm_start = warm_start;
+        self
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::Clamp` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Optimize the amplitudes of the transducers within [0, 1] as well as their phases.
+    /// The amplitude is parameterized as `(1 + sin s) / 2` so that the problem remains unconstrained.
+    pub fn with_amplitude_optimization(mut self, optimize_amplitude: bool) -> Self {
+        self.optimize_amplitude = optimize_amplitude;
+        self
+    }
+
+    fn amplitude(s: Float) -> Float {
+        0.5 * (1.0 + s.sin())
+    }
+
+    /// Emission of `j`-th transducer
+    fn emission(&self, x: &Array1<Float>, j: usize, n_b: usize) -> Complex {
+        let amp = if self.optimize_amplitude {
+            Self::amplitude(x[n_b + j])
+        } else {
+            1.0
+        };
+        amp * Complex::new(0., x[j]).exp()
+    }
+
+    /// Solve the damped normal equations by the conjugate gradient method without forming `B^H B`,
+    /// so that the memory and the time per iteration are proportional to the number of transducers.
+    /// The conjugate gradient method stops after `cg_max_iter` iterations or when the residual is reduced by `cg_tol`.
+    pub fn with_matrix_free(mut self, cg_max_iter: usize, cg_tol: Float) -> Self {
+        self.matrix_free = Some((cg_max_iter, cg_tol));
+        self
+    }
+
     fn adjoint(m: &Array2<Complex>) -> Array2<Complex> {
         m.t().mapv(|c| c.conj())
     }
 
+    /// The parameters are the phases of the transducers, the phases of the foci whose phase is free
+    /// and, if some phases are fixed, a reference phase which is kept zero.
+    /// The rows of B are the foci, the null points, the active upper bounds and the temporal regularization,
+    /// where the active upper bounds are treated as targets with fixed complex values,
+    /// and the regularization pulls each transducer toward its emission in the previous frame.
+    /// The rows of the foci and the null points are scaled by the square roots of their weights.
+    /// `G` is the transfer matrix to the control points of the targets,
+    /// and each active bound is given by its index and its target value.
+    /// Returns `B` and the index of the reference parameter.
     #[allow(non_snake_case)]
-    fn make_BhB(
-        amps: &[Float],
-        foci: &[Vector3],
-        wave_source: &mut [WaveSource],
+    fn make_B(
+        targets: &Targets,
+        G: &ArrayView2<Complex>,
+        active_bounds: &[(usize, Complex)],
+        prior: Option<(&[Complex], Float)>,
         n: usize,
         m: usize,
-    ) -> Array2<Complex> {
-        let mut P = Array::zeros((m, m));
-        let mut G = Array::zeros((m, n));
-        for i in 0..m {
-            P[[i, i]] = Complex::new(amps[i], 0.0);
-            let fp = foci[i];
-            for j in 0..n {
-                G[[i, j]] = transfer(wave_source[j].pos, fp);
+    ) -> (Array2<Complex>, Option<usize>) {
+        let amps = targets.amps();
+        let free: Vec<_> = (0..m).filter(|&i| targets.phase(i).is_none()).collect();
+        let reference = if targets.has_fixed_phase() || !active_bounds.is_empty() || prior.is_some()
+        {
+            Some(n + free.len())
+        } else {
+            None
+        };
+        let n_param = n + free.len() + reference.map_or(0, |_| 1);
+
+        let null_points = targets.null_points();
+        let m_null = m + null_points.len();
+        let m_bound = m_null + active_bounds.len();
+        let rows = m_bound + prior.map_or(0, |_| n);
+
+        let mut B = Array::zeros((rows, n_param));
+        B.slice_mut(s![0..m_null, 0..n])
+            .assign(&G.slice(s![0..m_null, ..]));
+        for (k, &w) in targets.null_weights().iter().enumerate() {
+            let w = w.sqrt();
+            B.slice_mut(s![m + k, 0..n]).mapv_inplace(|v| w * v);
+        }
+        for (k, &(l, _)) in active_bounds.iter().enumerate() {
+            B.slice_mut(s![m_null + k, 0..n]).assign(&G.row(m_null + l));
+        }
+        for (k, &i) in free.iter().enumerate() {
+            B[[i, n + k]] = Complex::new(-amps[i], 0.0);
+        }
+        if let Some(r) = reference {
+            for i in 0..m {
+                if let Some(t) = targets.complex_amp(i) {
+                    B[[i, r]] = -t;
+                }
+            }
+            for (k, &(_, t)) in active_bounds.iter().enumerate() {
+                B[[m_null + k, r]] = -t;
             }
+            if let Some((previous, weight)) = prior {
+                let w = weight.sqrt();
+                for j in 0..n {
+                    B[[m_bound + j, j]] = Complex::new(w, 0.);
+                    B[[m_bound + j, r]] = -w * previous[j];
+                }
+            }
+        }
+        for i in 0..m {
+            let w = targets.weight(i).sqrt();
+            B.row_mut(i).mapv_inplace(|v| w * v);
         }
-        let B = stack![Axis(1), G, -P];
-        Self::adjoint(&B).dot(&B)
+        (B, reference)
+    }
+
+    #[allow(non_snake_case)]
+    fn fix_param(JtJ: &mut Array2<Float>, Jtf: &mut Array1<Float>, idx: usize) {
+        JtJ.row_mut(idx).fill(0.0);
+        JtJ.column_mut(idx).fill(0.0);
+        Jtf[idx] = 0.0;
     }
 
     #[allow(non_snake_case)]
@@ -82,8 +228,8 @@ impl LM {
     ) {
         general_mat_mul(
             Complex::new(1., 0.),
-            &T,
-            &Self::adjoint(&T),
+            T,
+            &Self::adjoint(T),
             Complex::new(0., 0.),
             tmp_mat,
         );
@@ -106,80 +252,107 @@ impl LM {
         }
         Self::adjoint(&t).dot(BhB).dot(&t)[[0, 0]].re
     }
-}
-
-impl Optimizer for LM {
-    fn set_target_foci(&mut self, foci: &[Vector3]) {
-        self.foci = foci.to_vec();
-    }
-
-    fn set_target_amps(&mut self, amps: &[Float]) {
-        self.amps = amps.to_vec();
-    }
-
-    #[allow(non_snake_case, clippy::many_single_char_names)]
-    fn optimize(&self, wave_source: &mut [WaveSource]) {
-        let num_trans = wave_source.len();
-        let foci = &self.foci;
-        let amps = &self.amps;
-
-        let m = foci.len();
-        let n = num_trans;
 
-        let n_param = n + m;
-
-        let mut x0: ArrayBase<OwnedRepr<Float>, _> = Array::zeros(n_param);
-
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        for i in 0..n_param {
-            x0[i] = rng.gen::<Float>() * 2.0 * PI;
+    #[allow(non_snake_case)]
+    fn system(
+        &self,
+        B: Array2<Complex>,
+        reference: Option<usize>,
+        n: usize,
+    ) -> Box<dyn NormalEquations> {
+        let params = Params {
+            n_b: B.shape()[1],
+            n_amp: if self.optimize_amplitude { n } else { 0 },
+            reference,
+        };
+        match self.matrix_free {
+            Some((cg_max_iter, cg_tol)) => Box::new(MatrixFree {
+                du: Array::zeros(params.len()),
+                B,
+                params,
+                cg_max_iter,
+                cg_tol,
+            }),
+            None if self.optimize_amplitude => Box::new(DenseAmplitude {
+                A: Array::zeros((params.len(), params.len())),
+                B,
+                params,
+            }),
+            None => {
+                let BhB = Self::adjoint(&B).dot(&B);
+                let n_param = BhB.shape()[0];
+                Box::new(Dense {
+                    BhB,
+                    reference,
+                    A: Array::zeros((n_param, n_param)),
+                    T: Array::zeros((n_param, 1)),
+                    tmp: Array::zeros((n_param, n_param)),
+                })
+            }
         }
+    }
 
-        let I: ArrayBase<OwnedRepr<Float>, _> = Array::eye(n_param);
-
-        let BhB = Self::make_BhB(amps, foci, wave_source, n, m);
-
+    /// `done` is the number of iterations before this call, which is passed to the monitor along with `start`.
+    #[allow(non_snake_case)]
+    fn solve(
+        &self,
+        system: &mut dyn NormalEquations,
+        x0: Array1<Float>,
+        done: usize,
+        start: Instant,
+    ) -> Result<(Array1<Float>, usize, Convergence), OptimizeError> {
         let mut x = x0;
         let mut nu = 2.0;
 
-        let mut T = Array::zeros((n_param, 1));
-        Self::make_T(&mut T, &x, n_param);
-
-        let mut A = Array::zeros((n_param, n_param));
-        let mut g = Array::zeros(n_param);
-        let mut tmp = Array::zeros((n_param, n_param));
-        Self::calc_JtJ_Jtf(&mut A, &mut g, &BhB, &T, n_param, &mut tmp);
-        let A_max: Float = {
-            let mut tmp = Float::NEG_INFINITY;
-            for i in 0..n_param {
-                tmp = tmp.max(A[[i, i]]);
-            }
-            tmp
-        };
-        let mut mu = self.tau * A_max;
+        let mut g = system.linearize(&x);
+        let mut mu = self.tau * system.diag_max();
         let mut found = g.norm_max() <= self.eps_1;
-        let mut Fx = Self::calc_Fx(&BhB, &x, n_param);
+        let mut convergence = if found {
+            Convergence::GradientTolerance
+        } else {
+            Convergence::MaxIterations
+        };
+        let mut Fx = system.cost(&x);
         let mut x_new;
+        let mut iterations = 0;
         for _ in 0..self.k_max {
             if found {
                 break;
             }
+            iterations += 1;
 
-            let h_lm = -(&A + &(mu * &I)).solveh(&g).unwrap();
+            let h_lm = -system.solve_damped(mu, &g)?;
+            let mut record = self
+                .history
+                .as_ref()
+                .map(|_| Record::new(done + iterations, Fx, start));
+            if let Some(record) = record.as_mut() {
+                record.mu = Some(mu);
+                record.step_norm = Some(h_lm.norm());
+            }
             if h_lm.norm() <= self.eps_2 * (x.norm() + self.eps_2) {
                 found = true;
+                convergence = Convergence::StepTolerance;
             } else {
                 x_new = &x + &h_lm;
-                let Fx_new = Self::calc_Fx(&BhB, &x_new, n_param);
+                let Fx_new = system.cost(&x_new);
                 let L0_Lhlm = 0.5 * h_lm.t().dot(&(mu * &h_lm - &g));
                 let rho = (Fx - Fx_new) / L0_Lhlm;
-                Fx = Fx_new;
+                if let Some(record) = record.as_mut() {
+                    record.rho = Some(rho);
+                    record.accepted = Some(rho > 0.0);
+                    if rho > 0.0 {
+                        record.cost = Fx_new;
+                    }
+                }
                 if rho > 0.0 {
+                    Fx = Fx_new;
                     x = x_new;
-                    Self::make_T(&mut T, &x, n_param);
-                    Self::calc_JtJ_Jtf(&mut A, &mut g, &BhB, &T, n_param, &mut tmp);
+                    g = system.linearize(&x);
                     found = g.norm_max() <= self.eps_1;
+                    if found {
+                        convergence = Convergence::GradientTolerance;
+                    }
                     mu *= (1f64 / 3.).max(1. - (2. * rho - 1.).pow(3.));
                     nu = 2.0;
                 } else {
@@ -187,10 +360,480 @@ impl Optimizer for LM {
                     nu *= 2.0;
                 }
             }
+            if let (Some(history), Some(record)) = (&self.history, record.as_mut()) {
+                record.elapsed = start.elapsed().as_secs_f64();
+                history.push(record.clone());
+            }
+            if let Some(c) = self.monitor.check(done + iterations, start, || Fx) {
+                convergence = c;
+                break;
+            }
         }
 
-        for j in 0..n {
-            wave_source[j].q = Complex::new(0., x[j]).exp();
+        Ok((x, iterations, convergence))
+    }
+}
+
+/// Damped normal equations of the least squares `|B t(x)|^2`, where `t(x)_k = exp(i x_k)`
+trait NormalEquations {
+    fn cost(&self, x: &Array1<Float>) -> Float;
+    /// Linearize the problem around `x` and return the gradient
+    fn linearize(&mut self, x: &Array1<Float>) -> Array1<Float>;
+    /// Maximum diagonal element of `J^T J` at the last linearization
+    fn diag_max(&self) -> Float;
+    /// Solve `(J^T J + mu I) h = g` at the last linearization
+    fn solve_damped(&self, mu: Float, g: &Array1<Float>) -> Result<Array1<Float>, OptimizeError>;
+}
+
+/// Forms `B^H B` and `J^T J` explicitly, and solves the normal equations by the Cholesky decomposition
+#[allow(non_snake_case)]
+struct Dense {
+    BhB: Array2<Complex>,
+    reference: Option<usize>,
+    A: Array2<Float>,
+    T: Array2<Complex>,
+    tmp: Array2<Complex>,
+}
+
+impl NormalEquations for Dense {
+    fn cost(&self, x: &Array1<Float>) -> Float {
+        LM::calc_Fx(&self.BhB, x, self.BhB.shape()[0])
+    }
+
+    fn linearize(&mut self, x: &Array1<Float>) -> Array1<Float> {
+        let n_param = self.BhB.shape()[0];
+        let mut g = Array::zeros(n_param);
+        LM::make_T(&mut self.T, x, n_param);
+        LM::calc_JtJ_Jtf(
+            &mut self.A,
+            &mut g,
+            &self.BhB,
+            &self.T,
+            n_param,
+            &mut self.tmp,
+        );
+        if let Some(r) = self.reference {
+            LM::fix_param(&mut self.A, &mut g, r);
+        }
+        g
+    }
+
+    fn diag_max(&self) -> Float {
+        self.A
+            .diag()
+            .fold(Float::NEG_INFINITY, |acc, &a| acc.max(a))
+    }
+
+    #[allow(non_snake_case)]
+    fn solve_damped(&self, mu: Float, g: &Array1<Float>) -> Result<Array1<Float>, OptimizeError> {
+        let I: Array2<Float> = Array::eye(self.A.shape()[0]);
+        Ok((&self.A + &(mu * &I)).solveh(g)?)
+    }
+}
+
+/// Map from the parameters to the columns of `B`
+///
+/// The parameters are the phases of the columns of `B` followed by, in the amplitude mode,
+/// the amplitude parameters `s` of the transducers, where the amplitude is `(1 + sin s) / 2`.
+struct Params {
+    n_b: usize,
+    n_amp: usize,
+    reference: Option<usize>,
+}
+
+impl Params {
+    fn len(&self) -> usize {
+        self.n_b + self.n_amp
+    }
+
+    fn column(&self, k: usize) -> usize {
+        if k < self.n_b {
+            k
+        } else {
+            k - self.n_b
+        }
+    }
+
+    /// Returns the values of the columns and their derivatives by each parameter
+    fn linearize(&self, x: &Array1<Float>) -> (Array1<Complex>, Array1<Complex>) {
+        let mut u = x.slice(s![..self.n_b]).mapv(|x| Complex::new(0., x).exp());
+        let mut du = Array::zeros(self.len());
+        for k in 0..self.n_b {
+            du[k] = Complex::new(0., 1.) * u[k];
+        }
+        for j in 0..self.n_amp {
+            let s = x[self.n_b + j];
+            du[self.n_b + j] = 0.5 * s.cos() * u[j];
+            du[j] *= LM::amplitude(s);
+            u[j] *= LM::amplitude(s);
+        }
+        if let Some(r) = self.reference {
+            du[r] = Complex::new(0., 0.);
+        }
+        (u, du)
+    }
+}
+
+/// Keeps only `B` and solves the normal equations by the conjugate gradient method with the products of `J` and `J^T`
+#[allow(non_snake_case)]
+struct MatrixFree {
+    B: Array2<Complex>,
+    params: Params,
+    du: Array1<Complex>,
+    cg_max_iter: usize,
+    cg_tol: Float,
+}
+
+impl MatrixFree {
+    fn jv(&self, v: &Array1<Float>) -> Array1<Complex> {
+        let mut w = Array::zeros(self.params.n_b);
+        for k in 0..v.len() {
+            w[self.params.column(k)] += self.du[k] * v[k];
+        }
+        self.B.dot(&w)
+    }
+
+    fn jtw(&self, w: &Array1<Complex>) -> Array1<Float> {
+        // B^H w = conj(B^T conj(w)), without forming B^H
+        let bhw = self.B.t().dot(&w.mapv(|c| c.conj())).mapv(|c| c.conj());
+        Array::from_shape_fn(self.params.len(), |k| {
+            (self.du[k].conj() * bhw[self.params.column(k)]).re
+        })
+    }
+
+    /// `(J^T J + mu I) v`, where `J^T J = Re(J^H J)`
+    fn apply(&self, mu: Float, v: &Array1<Float>) -> Array1<Float> {
+        self.jtw(&self.jv(v)) + mu * v
+    }
+}
+
+impl NormalEquations for MatrixFree {
+    fn cost(&self, x: &Array1<Float>) -> Float {
+        let (u, _) = self.params.linearize(x);
+        self.B.dot(&u).iter().map(|r| r.norm_sqr()).sum()
+    }
+
+    fn linearize(&mut self, x: &Array1<Float>) -> Array1<Float> {
+        let (u, du) = self.params.linearize(x);
+        self.du = du;
+        self.jtw(&self.B.dot(&u))
+    }
+
+    fn diag_max(&self) -> Float {
+        (0..self.params.len())
+            .map(|k| {
+                let col = self.B.column(self.params.column(k));
+                col.iter().map(|b| b.norm_sqr()).sum::<Float>() * self.du[k].norm_sqr()
+            })
+            .fold(Float::NEG_INFINITY, Float::max)
+    }
+
+    fn solve_damped(&self, mu: Float, g: &Array1<Float>) -> Result<Array1<Float>, OptimizeError> {
+        let mut h = Array::zeros(g.len());
+        let mut r = g.clone();
+        let mut p = r.clone();
+        let mut rr = r.dot(&r);
+        let threshold = self.cg_tol * self.cg_tol * rr;
+        for _ in 0..self.cg_max_iter {
+            if rr <= threshold {
+                break;
+            }
+            let ap = self.apply(mu, &p);
+            let alpha = rr / p.dot(&ap);
+            h.scaled_add(alpha, &p);
+            r.scaled_add(-alpha, &ap);
+            let rr_new = r.dot(&r);
+            p = &r + &(rr_new / rr * &p);
+            rr = rr_new;
+        }
+        Ok(h)
+    }
+}
+
+/// Forms the Jacobian `J` explicitly for the amplitude mode, where `J^T J = Re(J^H J)`
+#[allow(non_snake_case)]
+struct DenseAmplitude {
+    B: Array2<Complex>,
+    params: Params,
+    A: Array2<Float>,
+}
+
+impl NormalEquations for DenseAmplitude {
+    fn cost(&self, x: &Array1<Float>) -> Float {
+        let (u, _) = self.params.linearize(x);
+        self.B.dot(&u).iter().map(|r| r.norm_sqr()).sum()
+    }
+
+    #[allow(non_snake_case)]
+    fn linearize(&mut self, x: &Array1<Float>) -> Array1<Float> {
+        let (u, du) = self.params.linearize(x);
+        let mut J = Array::zeros((self.B.shape()[0], self.params.len()));
+        for k in 0..self.params.len() {
+            let col = self.B.column(self.params.column(k));
+            J.column_mut(k).assign(&col.mapv(|b| b * du[k]));
+        }
+        let Jh = LM::adjoint(&J);
+        self.A = Jh.dot(&J).mapv(|v| v.re);
+        Jh.dot(&self.B.dot(&u)).mapv(|v| v.re)
+    }
+
+    fn diag_max(&self) -> Float {
+        self.A
+            .diag()
+            .fold(Float::NEG_INFINITY, |acc, &a| acc.max(a))
+    }
+
+    #[allow(non_snake_case)]
+    fn solve_damped(&self, mu: Float, g: &Array1<Float>) -> Result<Array1<Float>, OptimizeError> {
+        let I: Array2<Float> = Array::eye(self.A.shape()[0]);
+        Ok((&self.A + &(mu * &I)).solveh(g)?)
+    }
+}
+
+impl LM {
+    /// `prior` is the emissions of the previous frame and the weight of the temporal regularization
+    #[allow(non_snake_case, clippy::many_single_char_names)]
+    fn optimize_with(
+        &self,
+        wave_source: &mut [WaveSource],
+        prior: Option<(&[Complex], Float)>,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+        if let Some(history) = &self.history {
+            history.clear();
+        }
+
+        let num_trans = wave_source.len();
+
+        let m = self.targets.foci().len();
+        let n = num_trans;
+
+        // Rows are the foci, the null points and the upper bounds
+        let G = self
+            .propagation
+            .matrix(wave_source, &self.targets.control_points())?;
+        let G = G.matrix();
+        let m_null = m + self.targets.null_points().len();
+
+        let (B, reference) = Self::make_B(&self.targets, &G, &[], prior, n, m);
+        let mut n_b = B.shape()[1];
+        let mut system = self.system(B, reference, n);
+
+        let n_amp = if self.optimize_amplitude { n } else { 0 };
+        let mut x0: ArrayBase<OwnedRepr<Float>, _> = Array::zeros(n_b + n_amp);
+
+        if self.warm_start || prior.is_some() {
+            for j in 0..n {
+                x0[j] = wave_source[j].q.arg();
+            }
+            // The phases of the free foci are those of the current field
+            let field = self.propagation.field(wave_source, self.targets.foci())?;
+            let free = (0..m).filter(|&i| self.targets.phase(i).is_none());
+            for (k, i) in free.enumerate() {
+                x0[n + k] = field[i].arg();
+            }
+            // The amplitudes are kept away from 0 and 1, where their derivatives vanish
+            for j in 0..n_amp {
+                let a = c_norm(wave_source[j].q).clamp(0.05, 0.95);
+                x0[n_b + j] = (2.0 * a - 1.0).asin();
+            }
+        } else {
+            use rand::Rng;
+            let mut rng = rand::thread_rng();
+            for i in 0..n_b {
+                x0[i] = rng.gen::<Float>() * 2.0 * PI;
+            }
+            // The amplitudes start from about 0.85, not from 1, where their derivatives vanish
+            x0.slice_mut(s![n_b..]).fill(PI / 4.);
+        }
+        if let Some(r) = reference {
+            x0[r] = 0.0;
+        }
+        // Upper bounds are handled by an active set: a violated bound is added as a target at the limit.
+        let bound_limits = self.targets.bound_limits();
+        let mut active_bounds = vec![];
+        let mut active = vec![false; bound_limits.len()];
+        let mut iterations = 0;
+        let mut outer = 0;
+        let (x, convergence) = loop {
+            let (x, k, convergence) = self.solve(system.as_mut(), x0, iterations, start)?;
+            iterations += k;
+            if let Convergence::Stopped | Convergence::TimeBudget = convergence {
+                break (x, convergence);
+            }
+            outer += 1;
+
+            let mut violated = false;
+            for (l, &limit) in bound_limits.iter().enumerate() {
+                if active[l] {
+                    continue;
+                }
+                let g = G.row(m_null + l);
+                let p = (0..n).fold(Complex::new(0., 0.), |acc, j| {
+                    acc + g[j] * self.emission(&x, j, n_b)
+                });
+                if c_norm(p) > limit {
+                    active[l] = true;
+                    active_bounds.push((l, p / c_norm(p) * limit));
+                    violated = true;
+                }
+            }
+            if !violated || outer > MAX_ACTIVE_SET_ITER {
+                break (x, convergence);
+            }
+
+            let (B, reference) = Self::make_B(&self.targets, &G, &active_bounds, prior, n, m);
+            // A newly added reference parameter is appended to the phases and starts from zero
+            let new_n_b = B.shape()[1];
+            x0 = Array::zeros(new_n_b + n_amp);
+            x0.slice_mut(s![0..n_b]).assign(&x.slice(s![0..n_b]));
+            x0.slice_mut(s![new_n_b..]).assign(&x.slice(s![n_b..]));
+            n_b = new_n_b;
+            system = self.system(B, reference, n);
+        };
+
+        for (j, source) in wave_source.iter_mut().enumerate() {
+            source.q = self.emission(&x, j, n_b);
+        }
+        self.constraint.apply(wave_source)?;
+
+        // The error of the emissions after the constraint, without the prior, so that the costs of the optimizers are comparable
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+impl Optimizer for LM {
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
+    }
+
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        self.optimize_with(wave_source, None)
+    }
+}
+
+impl Temporal for LM {
+    fn optimize_regularized(
+        &self,
+        wave_source: &mut [WaveSource],
+        previous: &[Complex],
+        weight: Float,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        check_previous(wave_source, previous)?;
+        for (source, &q) in wave_source.iter_mut().zip(previous.iter()) {
+            source.q = q;
+        }
+        self.optimize_with(wave_source, Some((previous, weight)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sources() -> Vec<WaveSource> {
+        (0..16)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, Complex::new(0., 0.))
+            })
+            .collect()
+    }
+
+    fn lm() -> LM {
+        let mut lm = LM::new(1e-8, 1e-8, 1e-3, 50);
+        lm.set_target_foci(&[[10., 10., 100.], [20., 15., 100.]]);
+        lm.set_target_amps(&[1., 0.5]);
+        lm
+    }
+
+    #[test]
+    fn cost_in_history_never_increases() {
+        let history = History::new();
+        let lm = lm().with_history(history.clone());
+        let mut wave_source = sources();
+        lm.optimize(&mut wave_source).unwrap();
+
+        let records = history.records();
+        assert!(!records.is_empty());
+        for w in records.windows(2) {
+            assert!(w[1].cost <= w[0].cost);
+        }
+    }
+
+    #[test]
+    fn report_cost_is_error_of_emission() {
+        let lm = lm()
+            .with_amplitude_optimization(true)
+            .with_amplitude_constraint(AmplitudeConstraint::NormalizeToMax);
+        let mut wave_source = sources();
+        let report = lm.optimize(&mut wave_source).unwrap();
+        let error = amplitude_error(&Propagation::Analytic, lm.targets(), &wave_source).unwrap();
+        assert_eq!(report.cost, error);
+    }
+
+    #[test]
+    fn amplitude_optimization_reaches_weak_focus() {
+        // A single source at full amplitude overshoots the focus, which only its amplitude can fix.
+        // The warm start keeps the random phases from driving the amplitude to 0.
+        let focus = [0., 0., 100.];
+        let mut wave_source = vec![WaveSource::new([0., 0., 0.], Complex::new(1., 0.))];
+        let field = Propagation::Analytic.field(&wave_source, &[focus]).unwrap();
+        let mut lm = LM::new(1e-8, 1e-8, 1e-3, 50).with_warm_start(true);
+        lm.set_target_foci(&[focus]);
+        lm.set_target_amps(&[0.5 * field[0].norm()]);
+        let phase_only = lm.optimize(&mut wave_source.clone()).unwrap().cost;
+
+        let report = lm
+            .with_amplitude_optimization(true)
+            .optimize(&mut wave_source)
+            .unwrap();
+        assert!(report.cost < 1e-3 * phase_only);
+        assert!((wave_source[0].q.norm() - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn matrix_free_agrees_with_dense() {
+        // Both start from the same emissions instead of the random phases
+        let initial: Vec<_> = sources()
+            .into_iter()
+            .enumerate()
+            .map(|(j, s)| WaveSource::new(s.pos, Complex::new(0., 0.3 * j as Float).exp()))
+            .collect();
+        let mut dense = initial.clone();
+        let dense_cost = lm()
+            .with_warm_start(true)
+            .optimize(&mut dense)
+            .unwrap()
+            .cost;
+        let mut matrix_free = initial;
+        let matrix_free_cost = lm()
+            .with_warm_start(true)
+            .with_matrix_free(100, 1e-12)
+            .optimize(&mut matrix_free)
+            .unwrap()
+            .cost;
+        assert!((dense_cost - matrix_free_cost).abs() < 1e-6);
+        for (d, m) in dense.iter().zip(matrix_free.iter()) {
+            assert!((d.q - m.q).norm() < 1e-3);
         }
     }
 }
@@ -11,75 +11,119 @@
  *
  */
 
+use std::{sync::Arc, time::Instant};
+
 use crate::{
-    math_utils::c_norm, optimizer::Optimizer, utils::transfer, wave_source::WaveSource, Complex,
-    Float, Vector3,
+    math_utils::{c_norm, c_unit},
+    optimizer::{
+        report::amplitude_error, target::MAX_ACTIVE_SET_ITER, AmplitudeConstraint, Convergence,
+        OptimizeError, OptimizeReport, Optimizer, Targets,
+    },
+    transfer_matrix::{Propagation, TransferCache},
+    wave_source::WaveSource,
+    Complex, Float,
 };
 
 use ndarray::*;
 use ndarray_linalg::*;
 
 pub struct Long {
-    foci: Vec<Vector3>,
-    amps: Vec<Float>,
+    targets: Targets,
     gamma: Float,
+    constraint: AmplitudeConstraint,
+    propagation: Propagation,
 }
 
 impl Long {
     pub fn new(gamma: Float) -> Self {
         Self {
-            foci: vec![],
-            amps: vec![],
+            targets: Targets::new(),
             gamma,
+            constraint: AmplitudeConstraint::Clamp,
+            propagation: Propagation::Analytic,
         }
     }
 
+    /// Same as `with_propagation(Propagation::Cached(cache))`.
+    pub fn with_transfer_cache(mut self, cache: Arc<TransferCache>) -> Self {
+        self.propagation = Propagation::Cached(cache);
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::Clamp` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
     fn adjoint(m: &Array2<Complex>) -> Array2<Complex> {
         m.t().mapv(|c| c.conj())
     }
 }
 
 impl Optimizer for Long {
-    fn set_target_foci(&mut self, foci: &[Vector3]) {
-        self.foci = foci.to_vec();
+    fn targets(&self) -> &Targets {
+        &self.targets
     }
 
-    fn set_target_amps(&mut self, amps: &[Float]) {
-        self.amps = amps.to_vec();
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
     }
 
     #[allow(non_snake_case, clippy::many_single_char_names)]
-    fn optimize(&self, wave_source: &mut [WaveSource]) {
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+
         let num_trans = wave_source.len();
-        let foci = &self.foci;
-        let amps = &self.amps;
+        let foci = self.targets.foci();
+        let amps = self.targets.amps();
 
         let m = foci.len();
         let n = num_trans;
 
-        let mut X = Array::zeros((n, m));
-        let mut A = Array::zeros((m, n));
+        // Rows are the foci, the null points and the upper bounds
+        let T = self
+            .propagation
+            .matrix(wave_source, &self.targets.control_points())?;
+        let T = T.matrix();
+        let m_null = m + self.targets.null_points().len();
 
-        for i in 0..m {
-            let fp = foci[i];
-            for j in 0..n {
-                A[[i, j]] = transfer(wave_source[j].pos, fp);
-            }
-        }
+        let mut X = Array::zeros((n, m));
+        let A = T.slice(s![0..m, ..]).to_owned();
 
         for i in 0..m {
             let mut denomi = 0.0;
             for j in 0..n {
                 denomi += A[[i, j]].norm_sqr();
             }
+            // A focus not reached by any source is left out as in GS-PAT
+            if denomi == 0.0 {
+                continue;
+            }
+            let w = self.targets.weight(i);
             for j in 0..n {
-                X[[j, i]] = Complex::new(amps[i], 0.0) * A[[i, j]].conj() / denomi;
+                X[[j, i]] = Complex::new(w * amps[i], 0.0) * A[[i, j]].conj() / denomi;
             }
         }
 
         let R = A.dot(&X);
 
-        let (d, V) = R.eig().unwrap();
+        let (d, V) = R.eig()?;
         let mut max_value = 0.0;
         let mut max_idx = 0;
         for (j, &value) in d.iter().enumerate() {
@@ -91,9 +135,23 @@ impl Optimizer for Long {
         }
 
         let em_V = V.index_axis(Axis(0), max_idx);
+
+        // Rotate the eigenvector so that its phases fit the fixed phases of the targets
+        let rot = if self.targets.has_fixed_phase() {
+            (0..m)
+                .filter_map(|i| {
+                    self.targets
+                        .complex_amp(i)
+                        .map(|t| t * c_unit(em_V[i]).conj())
+                })
+                .fold(Complex::new(0., 0.), |acc, x| acc + x)
+                .arg()
+        } else {
+            0.0
+        };
         let mut e_arg = Array::zeros(m);
         for i in 0..m {
-            e_arg[i] = em_V[i].arg();
+            e_arg[i] = self.targets.phase(i).unwrap_or(em_V[i].arg() + rot);
         }
 
         let mut sigma = Array::zeros((n, n));
@@ -107,32 +165,87 @@ impl Optimizer for Long {
             sigma[[j, j]] = Complex::new(v, 0.0);
         }
 
-        let G = stack![Axis(0), A, sigma];
-        let mut f = Array::zeros(m + n);
+        let mut C = T.slice(s![m..m_null, ..]).to_owned();
+        for (k, &w) in self.targets.null_weights().iter().enumerate() {
+            let w = w.sqrt();
+            C.row_mut(k).mapv_inplace(|v| w * v);
+        }
+
+        let bound_limits = self.targets.bound_limits();
+        let D = T.slice(s![m_null.., ..]);
+
+        // Weighted least squares
+        let mut Aw = A.clone();
         for i in 0..m {
-            f[i] = amps[i] * (Complex::new(0., e_arg[i])).exp();
+            let w = self.targets.weight(i).sqrt();
+            Aw.row_mut(i).mapv_inplace(|v| w * v);
+        }
+
+        let G = stack![Axis(0), Aw, C, sigma];
+        let mut f = Array::zeros(m_null + n);
+        for i in 0..m {
+            f[i] = self.targets.weight(i).sqrt() * amps[i] * (Complex::new(0., e_arg[i])).exp();
         }
 
         let gt = Self::adjoint(&G);
         let gtg = gt.dot(&G);
         let gtf = gt.dot(&f);
-        let mut q = gtg.solve_into(gtf).unwrap();
+
+        // Upper bounds are handled by an active set: a violated bound is added as a target at the limit.
+        let mut active: Vec<(usize, Complex)> = vec![];
+        let mut iterations = 0;
+        let mut q = loop {
+            iterations += 1;
+            let mut gtg = gtg.clone();
+            let mut gtf = gtf.clone();
+            for &(k, t) in active.iter() {
+                let row = D.row(k);
+                for r in 0..n {
+                    for c in 0..n {
+                        gtg[[r, c]] += row[r].conj() * row[c];
+                    }
+                    gtf[r] += row[r].conj() * t;
+                }
+            }
+            let q = gtg.solve_into(gtf)?;
+
+            let pb = D.dot(&q);
+            let mut violated = false;
+            for (k, &limit) in bound_limits.iter().enumerate() {
+                if c_norm(pb[k]) > limit && active.iter().all(|&(a, _)| a != k) {
+                    active.push((k, pb[k] / c_norm(pb[k]) * limit));
+                    violated = true;
+                }
+            }
+            if !violated || iterations > MAX_ACTIVE_SET_ITER {
+                break q;
+            }
+        };
 
         // Correction provided in GS-PAT
         let zc = A.dot(&q);
         let ratio: Float = zc
             .iter()
             .zip(amps.iter())
-            .map(|(&az, &a0)| c_norm(az) / a0)
+            .enumerate()
+            .map(|(i, (&az, &a0))| self.targets.weight(i) * c_norm(az) / a0)
             .sum();
-        let avg_err = m as Float / ratio;
+        let total_weight: Float = (0..m).map(|i| self.targets.weight(i)).sum();
+        let avg_err = total_weight / ratio;
         for i in 0..n {
-            q[i] = q[i] / avg_err;
+            q[i] /= avg_err;
         }
 
         for j in 0..n {
-            let amp = c_norm(q[j]).min(1.0);
-            wave_source[j].q = q[j] / c_norm(q[j]) * amp;
+            wave_source[j].q = q[j];
         }
+        self.constraint.apply(wave_source)?;
+
+        Ok(OptimizeReport {
+            iterations,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence: Convergence::Completed,
+            elapsed: start.elapsed(),
+        })
     }
 }
@@ -11,22 +11,303 @@
  *
  */
 
+mod constraint;
+mod cost;
+mod error;
 pub mod ghrbf;
+mod gradient;
+mod gs;
 mod gs_pat;
+mod gs_pat_batch;
+mod history;
 mod horn;
 mod levenberg_marquardt;
 mod long;
+mod monitor;
+mod naive;
+mod pipeline;
+mod registry;
+mod report;
+mod sequence;
+mod target;
 
+pub use constraint::AmplitudeConstraint;
+pub use cost::{Cost, CostFn, CostKind};
+pub use error::OptimizeError;
 pub use ghrbf::*;
+pub use gradient::{Gradient, Objective, StepRule};
+pub use gs::GS;
 pub use gs_pat::GSPAT;
+pub use gs_pat_batch::{BatchReport, GSPATBatch};
+pub use history::{History, Record};
 pub use horn::Horn;
 pub use levenberg_marquardt::LM;
 pub use long::Long;
+pub use monitor::{Control, Monitor, ObserverFn, Progress};
+pub use naive::Naive;
+pub use pipeline::{Metric, Pipeline};
+pub use registry::{
+    algorithms, AlgorithmSchema, GSPATParams, GSParams, GradientParams, GreedyParams, HornParams,
+    LMParams, LongParams, NaiveParams, OptimizerSpec, ParamSchema, PipelineParams,
+};
+pub use report::{Convergence, OptimizeReport};
+pub use sequence::{Frame, Sequence, Temporal};
+pub use target::Targets;
 
-use crate::{wave_source::WaveSource, Float, Vector3};
+use crate::{
+    calibration::Calibration, transfer_matrix::Propagation, wave_source::WaveSource, Complex,
+    Float, Vector3,
+};
 
 pub trait Optimizer {
-    fn set_target_foci(&mut self, foci: &[Vector3]);
-    fn set_target_amps(&mut self, amps: &[Float]);
-    fn optimize(&self, wave_source: &mut [WaveSource]);
+    fn targets(&self) -> &Targets;
+    fn targets_mut(&mut self) -> &mut Targets;
+
+    /// Amplitude constraints applied to the result, which are replaced temporarily by `optimize_calibrated`.
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![]
+    }
+
+    /// Models of the propagation, whose imported matrices are cut down to the active sources by `optimize_masked`.
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![]
+    }
+
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError>;
+
+    /// Optimize only the wave sources whose `mask` is `true`.
+    /// The others are excluded from the optimization, i.e., from the transfer matrices and the greedy ordering, and their emissions are set to zero.
+    /// An imported transfer matrix has the columns for all the sources, and those of the active sources are used during the optimization.
+    fn optimize_masked(
+        &mut self,
+        wave_source: &mut [WaveSource],
+        mask: &[bool],
+    ) -> Result<OptimizeReport, OptimizeError> {
+        if mask.len() != wave_source.len() {
+            return Err(OptimizeError::SourceSizeMismatch {
+                target: "mask",
+                sources: wave_source.len(),
+                len: mask.len(),
+            });
+        }
+        let columns: Vec<_> = (0..mask.len()).filter(|&j| mask[j]).collect();
+        let mut active: Vec<_> = columns.iter().map(|&j| wave_source[j]).collect();
+        let originals: Vec<_> = self
+            .propagations_mut()
+            .into_iter()
+            .map(|p| {
+                let selected = p.select_sources(mask.len(), &columns);
+                std::mem::replace(p, selected)
+            })
+            .collect();
+        let report = self.optimize(&mut active);
+        for (p, original) in self.propagations_mut().into_iter().zip(originals) {
+            *p = original;
+        }
+        let report = report?;
+        let mut active = active.into_iter();
+        for (source, &m) in wave_source.iter_mut().zip(mask) {
+            match m {
+                true => *source = active.next().unwrap(),
+                false => source.q = Complex::new(0., 0.),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Optimize the emissions of the sources with `calibration`, and set their drives compensating it.
+    /// The current drives are converted into the emissions before the optimization for the warm starts.
+    ///
+    /// The emission of each source is bounded by its gain, so that it is reached with a drive of at most 1.
+    /// To this end, the amplitude constraints of the optimizer are replaced by `AmplitudeConstraint::PerTransducerMax`
+    /// during the optimization. The sources with zero gain are masked out.
+    fn optimize_calibrated(
+        &mut self,
+        wave_source: &mut [WaveSource],
+        calibration: &Calibration,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        let mask = vec![true; wave_source.len()];
+        self.optimize_masked_calibrated(wave_source, &mask, calibration)
+    }
+
+    /// `optimize_calibrated` on the wave sources whose `mask` is `true`, see also `optimize_masked`.
+    fn optimize_masked_calibrated(
+        &mut self,
+        wave_source: &mut [WaveSource],
+        mask: &[bool],
+        calibration: &Calibration,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        if calibration.len() != wave_source.len() {
+            return Err(OptimizeError::SourceSizeMismatch {
+                target: "calibration",
+                sources: wave_source.len(),
+                len: calibration.len(),
+            });
+        }
+        if mask.len() != wave_source.len() {
+            return Err(OptimizeError::SourceSizeMismatch {
+                target: "mask",
+                sources: wave_source.len(),
+                len: mask.len(),
+            });
+        }
+        let active: Vec<_> = mask
+            .iter()
+            .zip(calibration.gains())
+            .map(|(&m, &gain)| m && gain > 0.)
+            .collect();
+        let limits: Vec<_> = calibration
+            .gains()
+            .iter()
+            .zip(active.iter())
+            .filter(|(_, &a)| a)
+            .map(|(&gain, _)| gain)
+            .collect();
+        let originals: Vec<_> = self
+            .amplitude_constraints_mut()
+            .into_iter()
+            .map(|c| std::mem::replace(c, AmplitudeConstraint::PerTransducerMax(limits.clone())))
+            .collect();
+
+        calibration.emit(wave_source);
+        let report = self.optimize_masked(wave_source, &active);
+        calibration.compensate(wave_source);
+
+        for (c, original) in self.amplitude_constraints_mut().into_iter().zip(originals) {
+            *c = original;
+        }
+        report
+    }
+
+    fn set_target_foci(&mut self, foci: &[Vector3]) {
+        self.targets_mut().set_foci(foci);
+    }
+
+    fn set_target_amps(&mut self, amps: &[Float]) {
+        self.targets_mut().set_amps(amps);
+    }
+
+    /// Fix the phase at each focus. `None` leaves the phase free.
+    /// `Horn`, which leaves all the phases free, fails with `OptimizeError::UnsupportedTarget`.
+    fn set_target_phases(&mut self, phases: &[Option<Float>]) {
+        self.targets_mut().set_phases(phases);
+    }
+
+    /// Set the weight of each focus.
+    /// `Horn`, which weighs all the foci equally, fails with `OptimizeError::UnsupportedTarget`.
+    fn set_target_weights(&mut self, weights: &[Float]) {
+        self.targets_mut().set_weights(weights);
+    }
+
+    /// Set the amplitudes and the phases at all foci at once.
+    fn set_target_complex_amps(&mut self, values: &[Complex]) {
+        self.targets_mut().set_complex_amps(values);
+    }
+
+    /// Set the points to be kept quiet with their weights.
+    /// The optimizers which only take the foci into account, e.g., `GSPAT`, fail with `OptimizeError::UnsupportedControlPoints`.
+    fn set_null_points(&mut self, points: &[Vector3], weights: &[Float]) {
+        self.targets_mut().set_null_points(points, weights);
+    }
+
+    /// Set the points where the amplitude must not exceed the limits.
+    /// The optimizers which only take the foci into account fail as well as with the null points.
+    fn set_upper_bounds(&mut self, points: &[Vector3], limits: &[Float]) {
+        self.targets_mut().set_upper_bounds(points, limits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::Array;
+
+    fn sources(n: usize) -> Vec<WaveSource> {
+        (0..n)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, Complex::new(0., 0.))
+            })
+            .collect()
+    }
+
+    fn naive() -> Naive {
+        let mut naive = Naive::new();
+        naive.set_target_foci(&[[15., 15., 100.]]);
+        naive.set_target_amps(&[1.]);
+        naive
+    }
+
+    #[test]
+    fn masked_sources_are_turned_off() {
+        let mut wave_source = sources(8);
+        wave_source[1].q = Complex::new(1., 0.);
+        let mask: Vec<_> = (0..8).map(|i| i % 2 == 0).collect();
+        naive().optimize_masked(&mut wave_source, &mask).unwrap();
+        for (s, &m) in wave_source.iter().zip(mask.iter()) {
+            assert_eq!(s.q.norm_sqr() > 0., m);
+        }
+        assert!(matches!(
+            naive().optimize_masked(&mut wave_source, &mask[1..]),
+            Err(OptimizeError::SourceSizeMismatch { target: "mask", .. })
+        ));
+    }
+
+    #[test]
+    fn calibrated_drives_are_bounded_and_constraint_restored() {
+        let mut wave_source = sources(8);
+        let gains: Vec<_> = (0..8).map(|i| [0.5, 0., 1., 0.8][i % 4]).collect();
+        let calibration = Calibration::new(&[0.2; 8], &gains);
+        let mut naive = naive();
+        let report = naive
+            .optimize_calibrated(&mut wave_source, &calibration)
+            .unwrap();
+        for (s, &gain) in wave_source.iter().zip(gains.iter()) {
+            assert!(s.q.norm() <= 1. + 1e-9);
+            if gain == 0. {
+                assert_eq!(s.q, Complex::new(0., 0.));
+            }
+        }
+        let mut emission = wave_source.clone();
+        calibration.emit(&mut emission);
+        let error =
+            report::amplitude_error(&Propagation::Analytic, naive.targets(), &emission).unwrap();
+        assert!((report.cost - error).abs() <= 1e-9 * error.max(1.));
+        assert_eq!(
+            *naive.amplitude_constraints_mut()[0],
+            AmplitudeConstraint::NormalizeToMax
+        );
+        assert!(matches!(
+            naive.optimize_calibrated(&mut wave_source, &Calibration::identity(4)),
+            Err(OptimizeError::SourceSizeMismatch {
+                target: "calibration",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn imported_matrix_is_cut_down_to_active_sources() {
+        // The phase of each column tells whether the right column is used for each source
+        let matrix =
+            Array::from_shape_fn((1, 8), |(_, j)| Complex::new(0., 0.3 * j as Float).exp());
+        let mut naive = naive().with_propagation(Propagation::imported(matrix.clone()));
+        let mut gains = vec![1.; 8];
+        gains[1] = 0.;
+        let calibration = Calibration::new(&[0.; 8], &gains);
+
+        let mut wave_source = sources(8);
+        naive
+            .optimize_calibrated(&mut wave_source, &calibration)
+            .unwrap();
+        assert_eq!(wave_source[1].q, Complex::new(0., 0.));
+        for (j, s) in wave_source.iter().enumerate().filter(|&(j, _)| j != 1) {
+            let d = s.q * matrix[[0, j]];
+            assert!(d.im.abs() < 1e-9 && d.re > 0., "source {}: {}", j, s.q);
+        }
+        assert!(matches!(
+            &*naive.propagations_mut()[0],
+            Propagation::Imported(m) if m.ncols() == 8
+        ));
+    }
 }
@@ -0,0 +1,128 @@
+/*
+ * File: monitor.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{optimizer::Convergence, Float};
+
+/// State of an optimizer passed to the observer
+#[derive(Debug, Clone, Copy)]
+pub struct Progress {
+    /// Number of iterations (or updated sources for the greedy algorithm) so far
+    pub iteration: usize,
+    /// Current value of the cost function of the optimizer
+    pub cost: Float,
+    /// Wall-clock time since the optimization started
+    pub elapsed: Duration,
+}
+
+/// Reply of the observer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+pub type ObserverFn = dyn Fn(&Progress) -> Control + Send + Sync;
+
+/// Observer called at each iteration, and the wall-clock time budget
+///
+/// An optimizer stopped by the monitor still applies its amplitude constraint and returns its current result,
+/// with `Convergence::Stopped` or `Convergence::TimeBudget` in the report.
+#[derive(Clone, Default)]
+pub struct Monitor {
+    observer: Option<Arc<ObserverFn>>,
+    time_budget: Option<Duration>,
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&Progress) -> Control + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Called after each iteration. `cost` is evaluated only if an observer is set.
+    /// Returns the reason to stop if the optimizer should stop.
+    pub(crate) fn check<F: FnOnce() -> Float>(
+        &self,
+        iteration: usize,
+        start: Instant,
+        cost: F,
+    ) -> Option<Convergence> {
+        if let Some(observer) = &self.observer {
+            let progress = Progress {
+                iteration,
+                cost: cost(),
+                elapsed: start.elapsed(),
+            };
+            if observer(&progress) == Control::Stop {
+                return Some(Convergence::Stopped);
+            }
+        }
+        self.check_time_budget(start)
+    }
+
+    /// Check only the time budget without calling the observer.
+    pub(crate) fn check_time_budget(&self, start: Instant) -> Option<Convergence> {
+        match self.time_budget {
+            Some(budget) if start.elapsed() >= budget => Some(Convergence::TimeBudget),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cost_is_evaluated_only_with_observer() {
+        let start = Instant::now();
+        let monitor = Monitor::new();
+        assert_eq!(monitor.check(0, start, || panic!("evaluated")), None);
+
+        let monitor = Monitor::new().with_observer(|progress| match progress.cost < 1. {
+            true => Control::Stop,
+            false => Control::Continue,
+        });
+        assert_eq!(monitor.check(0, start, || 2.), None);
+        assert_eq!(monitor.check(1, start, || 0.5), Some(Convergence::Stopped));
+    }
+
+    #[test]
+    fn time_budget() {
+        let start = Instant::now();
+        let monitor = Monitor::new().with_time_budget(Duration::from_secs(0));
+        assert_eq!(
+            monitor.check_time_budget(start),
+            Some(Convergence::TimeBudget)
+        );
+        let monitor = Monitor::new().with_time_budget(Duration::from_secs(3600));
+        assert_eq!(monitor.check(0, start, || 0.), None);
+    }
+}
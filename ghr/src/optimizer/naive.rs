@@ -0,0 +1,139 @@
+/*
+ * File: naive.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::time::Instant;
+
+use crate::{
+    math_utils::c_unit,
+    optimizer::{
+        report::amplitude_error, AmplitudeConstraint, Convergence, OptimizeError, OptimizeReport,
+        Optimizer, Targets,
+    },
+    transfer_matrix::Propagation,
+    wave_source::WaveSource,
+    Complex,
+};
+
+/// Linear superposition of the single-focus solutions
+///
+/// Each source emits the sum of the phases which focus it at each focus, weighted by the target amplitudes.
+/// The emissions are normalized so that the largest amplitude becomes 1 by default.
+pub struct Naive {
+    targets: Targets,
+    constraint: AmplitudeConstraint,
+    propagation: Propagation,
+}
+
+impl Naive {
+    pub fn new() -> Self {
+        Self {
+            targets: Targets::new(),
+            constraint: AmplitudeConstraint::NormalizeToMax,
+            propagation: Propagation::Analytic,
+        }
+    }
+
+    /// Constraint on the amplitudes of the result. `AmplitudeConstraint::NormalizeToMax` is used by default.
+    pub fn with_amplitude_constraint(mut self, constraint: AmplitudeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Model of the propagation to the control points. See `Propagation`.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+}
+
+impl Default for Naive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimizer for Naive {
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        vec![&mut self.constraint]
+    }
+
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        vec![&mut self.propagation]
+    }
+
+    #[allow(non_snake_case)]
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+        self.targets.check_foci_only("Naive")?;
+
+        let foci = self.targets.foci();
+        let amps = self.targets.amps();
+        let G = self.propagation.matrix(wave_source, foci)?;
+        let G = G.matrix();
+
+        for (j, source) in wave_source.iter_mut().enumerate() {
+            let mut q = Complex::new(0., 0.);
+            for i in 0..foci.len() {
+                let g = G[[i, j]];
+                let phase = self.targets.phase(i).unwrap_or(0.);
+                q += self.targets.weight(i)
+                    * amps[i]
+                    * Complex::new(0., phase).exp()
+                    * c_unit(g).conj();
+            }
+            source.q = q;
+        }
+        self.constraint.apply(wave_source)?;
+
+        Ok(OptimizeReport {
+            iterations: 1,
+            cost: amplitude_error(&self.propagation, &self.targets, wave_source)?,
+            convergence: Convergence::Completed,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Float;
+    use ndarray::{s, Array};
+
+    #[test]
+    fn unreachable_focus_gives_finite_emissions() {
+        // The second focus is not reached by any source, and the last source reaches no focus
+        let mut matrix = Array::zeros((2, 4));
+        matrix.slice_mut(s![0, ..3]).fill(Complex::new(1., 0.));
+        let mut naive = Naive::new().with_propagation(Propagation::imported(matrix));
+        naive.set_target_foci(&[[0., 0., 100.], [0., 0., 200.]]);
+        naive.set_target_amps(&[1., 1.]);
+
+        let mut wave_source: Vec<_> = (0..4)
+            .map(|i| WaveSource::new([i as Float * 10., 0., 0.], Complex::new(0., 0.)))
+            .collect();
+        naive.optimize(&mut wave_source).unwrap();
+        assert!(wave_source
+            .iter()
+            .all(|s| s.q.re.is_finite() && s.q.im.is_finite()));
+    }
+}
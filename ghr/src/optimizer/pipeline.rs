@@ -0,0 +1,262 @@
+/*
+ * File: pipeline.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{sync::Mutex, time::Instant};
+
+use crate::{
+    optimizer::{
+        report::amplitude_error, AmplitudeConstraint, Convergence, Cost, Monitor, OptimizeError,
+        OptimizeReport, Optimizer, Targets,
+    },
+    transfer_matrix::Propagation,
+    wave_source::WaveSource,
+    Float,
+};
+
+/// Metric to compare the results of the stages of a pipeline
+#[derive(Clone)]
+pub enum Metric {
+    /// Sum of squared errors at the control points, which is the cost of the optimizers without an explicit objective
+    AmplitudeError,
+    /// Weighted sum of the errors at the foci
+    Cost(Cost),
+    /// Cost in the report of each stage, which is the squared error of its result for the built-in optimizers
+    Report,
+}
+
+impl Metric {
+    fn evaluate(
+        &self,
+        propagation: &Propagation,
+        targets: &Targets,
+        wave_source: &[WaveSource],
+        report: &OptimizeReport,
+    ) -> Result<Float, OptimizeError> {
+        Ok(match self {
+            Metric::AmplitudeError => amplitude_error(propagation, targets, wave_source)?,
+            Metric::Cost(cost) => {
+                cost.check(targets.amps())?;
+                propagation
+                    .field(wave_source, targets.foci())?
+                    .into_iter()
+                    .zip(targets.amps().iter())
+                    .enumerate()
+                    .map(|(i, (p, &amp))| {
+                        targets.weight(i) * cost.error(p, amp, targets.complex_amp(i))
+                    })
+                    .sum()
+            }
+            Metric::Report => report.cost,
+        })
+    }
+}
+
+/// Optimizers run in order on the same wave sources
+///
+/// Every stage is given the targets of the pipeline, and starts from the emissions left by the previous stage.
+/// Note that most optimizers start from scratch unless their warm start is enabled,
+/// e.g., `GSPAT` followed by `LM::new(..).with_warm_start(true)`.
+pub struct Pipeline {
+    targets: Targets,
+    stages: Vec<Mutex<Box<dyn Optimizer + Send>>>,
+    keep_best: Option<Metric>,
+    monitor: Monitor,
+    propagation: Propagation,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self {
+            targets: Targets::new(),
+            stages: vec![],
+            keep_best: None,
+            monitor: Monitor::new(),
+            propagation: Propagation::Analytic,
+        }
+    }
+
+    /// Append `optimizer` to the stages.
+    pub fn then<O: Optimizer + Send + 'static>(self, optimizer: O) -> Self {
+        self.then_boxed(Box::new(optimizer))
+    }
+
+    pub fn then_boxed(mut self, optimizer: Box<dyn Optimizer + Send>) -> Self {
+        self.stages.push(Mutex::new(optimizer));
+        self
+    }
+
+    /// Restore the emissions of the best stage by `metric` instead of those of the last stage.
+    pub fn with_keep_best(mut self, metric: Metric) -> Self {
+        self.keep_best = Some(metric);
+        self
+    }
+
+    /// The remaining stages are skipped when the time budget of `monitor` runs out.
+    /// The observer is not called by the pipeline, so the stages should be given their own monitors to be observed.
+    /// A stage stopped by its monitor also skips the remaining stages.
+    pub fn with_monitor(mut self, monitor: Monitor) -> Self {
+        self.monitor = monitor;
+        self
+    }
+
+    /// Model of the propagation used to evaluate the metrics. The stages are not affected, and have their own.
+    pub fn with_propagation(mut self, propagation: Propagation) -> Self {
+        self.propagation = propagation;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Optimizer for Pipeline {
+    fn targets(&self) -> &Targets {
+        &self.targets
+    }
+
+    fn targets_mut(&mut self) -> &mut Targets {
+        &mut self.targets
+    }
+
+    /// Constraints of all the stages
+    fn amplitude_constraints_mut(&mut self) -> Vec<&mut AmplitudeConstraint> {
+        self.stages
+            .iter_mut()
+            .flat_map(|stage| stage.get_mut().unwrap().amplitude_constraints_mut())
+            .collect()
+    }
+
+    /// Propagations of all the stages and that of the metrics
+    fn propagations_mut(&mut self) -> Vec<&mut Propagation> {
+        let mut propagations: Vec<_> = self
+            .stages
+            .iter_mut()
+            .flat_map(|stage| stage.get_mut().unwrap().propagations_mut())
+            .collect();
+        propagations.push(&mut self.propagation);
+        propagations
+    }
+
+    /// The cost of the report is that of the selected stage, measured by the metric if `with_keep_best` is set.
+    fn optimize(&self, wave_source: &mut [WaveSource]) -> Result<OptimizeReport, OptimizeError> {
+        let start = Instant::now();
+        self.targets.check(wave_source, 1)?;
+
+        let mut iterations = 0;
+        let mut result: Option<OptimizeReport> = None;
+        let mut best: Option<(Float, Vec<WaveSource>)> = None;
+        let mut stopped = None;
+        for stage in self.stages.iter() {
+            let mut stage = stage.lock().unwrap();
+            *stage.targets_mut() = self.targets.clone();
+            let mut report = stage.optimize(wave_source)?;
+            iterations += report.iterations;
+
+            if let Some(metric) = &self.keep_best {
+                report.cost =
+                    metric.evaluate(&self.propagation, &self.targets, wave_source, &report)?;
+                let improved = match &best {
+                    Some((cost, _)) => report.cost < *cost,
+                    None => true,
+                };
+                if improved {
+                    best = Some((report.cost, wave_source.to_vec()));
+                    result = Some(report.clone());
+                }
+            } else {
+                result = Some(report.clone());
+            }
+
+            stopped = match report.convergence {
+                Convergence::Stopped | Convergence::TimeBudget => Some(report.convergence),
+                _ => self.monitor.check_time_budget(start),
+            };
+            if stopped.is_some() {
+                break;
+            }
+        }
+
+        if let Some((_, snapshot)) = best {
+            wave_source.copy_from_slice(&snapshot);
+        }
+
+        let (cost, convergence) = match result {
+            Some(report) => (report.cost, stopped.unwrap_or(report.convergence)),
+            None => (
+                amplitude_error(&self.propagation, &self.targets, wave_source)?,
+                Convergence::Completed,
+            ),
+        };
+        Ok(OptimizeReport {
+            iterations,
+            cost,
+            convergence,
+            elapsed: start.elapsed(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+    use crate::{
+        optimizer::{Control, GSPAT},
+        Complex,
+    };
+
+    #[test]
+    fn stopped_stage_skips_the_rest() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let monitor = Monitor::new().with_observer(move |progress| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match progress.iteration {
+                3 => Control::Stop,
+                _ => Control::Continue,
+            }
+        });
+        let mut pipeline = Pipeline::new()
+            .then(GSPAT::new(10).with_monitor(monitor.clone()))
+            .then(GSPAT::new(10).with_monitor(monitor.clone()))
+            .with_monitor(monitor);
+        pipeline.set_target_foci(&[[0., 0., 100.]]);
+        pipeline.set_target_amps(&[1.]);
+
+        let mut wave_source: Vec<_> = (0..16)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, Complex::new(0., 0.))
+            })
+            .collect();
+        let report = pipeline.optimize(&mut wave_source).unwrap();
+        assert_eq!(report.convergence, Convergence::Stopped);
+        assert_eq!(report.iterations, 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,523 @@
+/*
+ * File: registry.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    optimizer::{
+        AmplitudeConstraint, CostKind, Gradient, GreedyBruteForce, Horn, Long, Metric, Monitor,
+        Naive, Objective, Optimizer, Pipeline, StepRule, GS, GSPAT, LM,
+    },
+    transfer_matrix::Propagation,
+    Float,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NaiveParams {
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for NaiveParams {
+    fn default() -> Self {
+        Self {
+            constraint: AmplitudeConstraint::NormalizeToMax,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GSParams {
+    pub repeat: usize,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for GSParams {
+    fn default() -> Self {
+        Self {
+            repeat: 100,
+            constraint: AmplitudeConstraint::PhaseOnly,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GSPATParams {
+    pub repeat: usize,
+    pub warm_start: bool,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for GSPATParams {
+    fn default() -> Self {
+        Self {
+            repeat: 100,
+            warm_start: false,
+            constraint: AmplitudeConstraint::Clamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HornParams {
+    pub repeat: usize,
+    pub alpha: Float,
+    pub lambda: Float,
+    pub warm_start: bool,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for HornParams {
+    fn default() -> Self {
+        Self {
+            repeat: 1000,
+            alpha: 1e-3,
+            lambda: 0.9,
+            warm_start: false,
+            constraint: AmplitudeConstraint::NormalizeToMax,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LongParams {
+    pub gamma: Float,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for LongParams {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            constraint: AmplitudeConstraint::Clamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LMParams {
+    pub eps_1: Float,
+    pub eps_2: Float,
+    pub tau: Float,
+    pub k_max: usize,
+    /// Maximum iterations and tolerance of the conjugate gradient, or `null` to use the dense solver
+    pub matrix_free: Option<(usize, Float)>,
+    pub optimize_amplitude: bool,
+    pub warm_start: bool,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for LMParams {
+    fn default() -> Self {
+        Self {
+            eps_1: 1e-8,
+            eps_2: 1e-8,
+            tau: 1e-3,
+            k_max: 200,
+            matrix_free: None,
+            optimize_amplitude: false,
+            warm_start: false,
+            constraint: AmplitudeConstraint::Clamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GreedyParams {
+    pub phase_division: usize,
+    pub amp_division: usize,
+    pub randomize: bool,
+    pub parallel: bool,
+    pub refine_passes: usize,
+    pub continuous: bool,
+    pub warm_start: bool,
+    pub cost: CostKind,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for GreedyParams {
+    fn default() -> Self {
+        Self {
+            phase_division: 16,
+            amp_division: 16,
+            randomize: false,
+            parallel: false,
+            refine_passes: 0,
+            continuous: false,
+            warm_start: false,
+            cost: CostKind::Complex,
+            constraint: AmplitudeConstraint::Clamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GradientParams {
+    pub k_max: usize,
+    pub tol: Float,
+    pub objective: Objective,
+    pub step_rule: StepRule,
+    /// Range of the amplitudes, or `null` to optimize only the phases
+    pub amplitude: Option<(Float, Float)>,
+    pub constraint: AmplitudeConstraint,
+}
+
+impl Default for GradientParams {
+    fn default() -> Self {
+        Self {
+            k_max: 200,
+            tol: 1e-8,
+            objective: Objective::Amplitude,
+            step_rule: StepRule::lbfgs(10),
+            amplitude: None,
+            constraint: AmplitudeConstraint::Clamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineParams {
+    pub stages: Vec<OptimizerSpec>,
+    /// Keep the best stage by `Metric::AmplitudeError` instead of the last one
+    pub keep_best: bool,
+}
+
+/// Specification of an optimizer, e.g., `{ "kind": "gspat", "repeat": 100 }`
+///
+/// The omitted parameters take their default values listed by `algorithms`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum OptimizerSpec {
+    #[serde(rename = "naive")]
+    Naive(NaiveParams),
+    #[serde(rename = "gs")]
+    GS(GSParams),
+    #[serde(rename = "gspat")]
+    GSPAT(GSPATParams),
+    #[serde(rename = "horn")]
+    Horn(HornParams),
+    #[serde(rename = "long")]
+    Long(LongParams),
+    #[serde(rename = "lm")]
+    LM(LMParams),
+    #[serde(rename = "greedy")]
+    Greedy(GreedyParams),
+    #[serde(rename = "gradient")]
+    Gradient(GradientParams),
+    #[serde(rename = "pipeline")]
+    Pipeline(PipelineParams),
+}
+
+impl OptimizerSpec {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OptimizerSpec::Naive(_) => "naive",
+            OptimizerSpec::GS(_) => "gs",
+            OptimizerSpec::GSPAT(_) => "gspat",
+            OptimizerSpec::Horn(_) => "horn",
+            OptimizerSpec::Long(_) => "long",
+            OptimizerSpec::LM(_) => "lm",
+            OptimizerSpec::Greedy(_) => "greedy",
+            OptimizerSpec::Gradient(_) => "gradient",
+            OptimizerSpec::Pipeline(_) => "pipeline",
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Optimizer + Send> {
+        self.build_with_monitor(Monitor::new())
+    }
+
+    /// Build the optimizer observed by `monitor`. `naive` and `long`, which do not iterate, ignore it.
+    ///
+    /// Each stage of `pipeline` is given its own clone of `monitor`, so the stages share the observer,
+    /// which sees the iterations of each stage counted from 1.
+    /// The time budget is counted from the start of each stage, and the pipeline skips the remaining stages
+    /// once the budget from its own start runs out. Thus the last stage may run over the budget of the whole pipeline by up to its own budget.
+    pub fn build_with_monitor(&self, monitor: Monitor) -> Box<dyn Optimizer + Send> {
+        self.build_with(monitor, Propagation::Analytic)
+    }
+
+    /// Build the optimizer observed by `monitor` with the model of the propagation `propagation`,
+    /// which is shared by the stages of `pipeline`.
+    pub fn build_with(
+        &self,
+        monitor: Monitor,
+        propagation: Propagation,
+    ) -> Box<dyn Optimizer + Send> {
+        match self.clone() {
+            OptimizerSpec::Naive(p) => Box::new(
+                Naive::new()
+                    .with_amplitude_constraint(p.constraint)
+                    .with_propagation(propagation),
+            ),
+            OptimizerSpec::GS(p) => Box::new(
+                GS::new(p.repeat)
+                    .with_amplitude_constraint(p.constraint)
+                    .with_monitor(monitor)
+                    .with_propagation(propagation),
+            ),
+            OptimizerSpec::GSPAT(p) => Box::new(
+                GSPAT::new(p.repeat)
+                    .with_warm_start(p.warm_start)
+                    .with_amplitude_constraint(p.constraint)
+                    .with_monitor(monitor)
+                    .with_propagation(propagation),
+            ),
+            OptimizerSpec::Horn(p) => Box::new(
+                Horn::new(p.repeat, p.alpha, p.lambda)
+                    .with_warm_start(p.warm_start)
+                    .with_amplitude_constraint(p.constraint)
+                    .with_monitor(monitor)
+                    .with_propagation(propagation),
+            ),
+            OptimizerSpec::Long(p) => Box::new(
+                Long::new(p.gamma)
+                    .with_amplitude_constraint(p.constraint)
+                    .with_propagation(propagation),
+            ),
+            OptimizerSpec::LM(p) => {
+                let mut lm = LM::new(p.eps_1, p.eps_2, p.tau, p.k_max)
+                    .with_amplitude_optimization(p.optimize_amplitude)
+                    .with_warm_start(p.warm_start)
+                    .with_amplitude_constraint(p.constraint)
+                    .with_monitor(monitor)
+                    .with_propagation(propagation);
+                if let Some((cg_max_iter, cg_tol)) = p.matrix_free {
+                    lm = lm.with_matrix_free(cg_max_iter, cg_tol);
+                }
+                Box::new(lm)
+            }
+            OptimizerSpec::Greedy(p) => Box::new(
+                GreedyBruteForce::new(p.phase_division, p.amp_division, p.randomize)
+                    .with_parallel(p.parallel)
+                    .with_refinement(p.refine_passes)
+                    .with_continuous_refinement(p.continuous)
+                    .with_warm_start(p.warm_start)
+                    .with_cost(p.cost.into())
+                    .with_amplitude_constraint(p.constraint)
+                    .with_monitor(monitor)
+                    .with_propagation(propagation),
+            ),
+            OptimizerSpec::Gradient(p) => {
+                let mut gradient = Gradient::new(p.k_max, p.tol)
+                    .with_objective(p.objective)
+                    .with_step_rule(p.step_rule)
+                    .with_amplitude_constraint(p.constraint)
+                    .with_monitor(monitor)
+                    .with_propagation(propagation);
+                if let Some((amp_min, amp_max)) = p.amplitude {
+                    gradient = gradient.with_amplitude(amp_min, amp_max);
+                }
+                Box::new(gradient)
+            }
+            OptimizerSpec::Pipeline(p) => {
+                let mut pipeline = p
+                    .stages
+                    .iter()
+                    .fold(Pipeline::new(), |pipeline, stage| {
+                        pipeline.then_boxed(stage.build_with(monitor.clone(), propagation.clone()))
+                    })
+                    .with_monitor(monitor)
+                    .with_propagation(propagation);
+                if p.keep_best {
+                    pipeline = pipeline.with_keep_best(Metric::AmplitudeError);
+                }
+                Box::new(pipeline)
+            }
+        }
+    }
+
+    /// Specifications of all algorithms with their default parameters
+    pub fn defaults() -> Vec<OptimizerSpec> {
+        vec![
+            OptimizerSpec::Naive(Default::default()),
+            OptimizerSpec::GS(Default::default()),
+            OptimizerSpec::GSPAT(Default::default()),
+            OptimizerSpec::Horn(Default::default()),
+            OptimizerSpec::Long(Default::default()),
+            OptimizerSpec::LM(Default::default()),
+            OptimizerSpec::Greedy(Default::default()),
+            OptimizerSpec::Gradient(Default::default()),
+            OptimizerSpec::Pipeline(Default::default()),
+        ]
+    }
+}
+
+/// Parameter of an algorithm
+#[derive(Debug, Clone, Serialize)]
+pub struct ParamSchema {
+    pub name: String,
+    /// JSON type of the parameter, e.g., "integer", "number" or "boolean"
+    #[serde(rename = "type")]
+    pub type_name: &'static str,
+    pub default: Value,
+}
+
+/// Algorithm available in `OptimizerSpec`
+#[derive(Debug, Clone, Serialize)]
+pub struct AlgorithmSchema {
+    pub kind: &'static str,
+    pub description: &'static str,
+    pub params: Vec<ParamSchema>,
+}
+
+fn description(kind: &str) -> &'static str {
+    match kind {
+        "naive" => "Superposition of the single-focus solutions",
+        "gs" => "Gerchberg-Saxton algorithm",
+        "gspat" => "GS-PAT (Plasencia et al. 2020)",
+        "horn" => "Semidefinite relaxation solved by the block coordinate descent",
+        "long" => "Eigenvector method with the regularized least squares (Long et al. 2014)",
+        "lm" => "Levenberg-Marquardt method",
+        "greedy" => "Greedy brute-force search over the discretized phases and amplitudes",
+        "gradient" => "First-order method with the analytic gradient (L-BFGS or Adam)",
+        "pipeline" => "Stages run in order on the same wave sources",
+        _ => "",
+    }
+}
+
+/// Type of a parameter, inferred from its default value except for those which are optional or have variants
+fn type_name(name: &str, value: &Value) -> &'static str {
+    const CONSTRAINT: &str =
+        "phase_only | clamp | normalize_to_max | uniform_scale | { per_transducer_max: [number] }";
+    const STEP_RULE: &str =
+        "{ rule: lbfgs, memory } | { rule: adam, learning_rate, beta_1, beta_2, eps }";
+    match (name, value) {
+        ("constraint", _) => CONSTRAINT,
+        ("step_rule", _) => STEP_RULE,
+        ("objective", _) => "amplitude | intensity",
+        ("cost", _) => "l1 | l2 | intensity | relative | complex",
+        ("matrix_free", _) => "[integer, number] | null",
+        ("amplitude", _) => "[number, number] | null",
+        ("stages", _) => "[spec]",
+        (_, Value::Null) => "null",
+        (_, Value::Bool(_)) => "boolean",
+        (_, Value::Number(n)) if n.is_u64() || n.is_i64() => "integer",
+        (_, Value::Number(_)) => "number",
+        (_, Value::String(_)) => "string",
+        (_, Value::Array(_)) => "array",
+        (_, Value::Object(_)) => "object",
+    }
+}
+
+/// List the available algorithms with their parameters and the default values.
+pub fn algorithms() -> Vec<AlgorithmSchema> {
+    OptimizerSpec::defaults()
+        .iter()
+        .map(|spec| {
+            let params = match serde_json::to_value(spec).unwrap() {
+                Value::Object(map) => map
+                    .into_iter()
+                    .filter(|(name, _)| name != "kind")
+                    .map(|(name, default)| ParamSchema {
+                        type_name: type_name(&name, &default),
+                        name,
+                        default,
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            AlgorithmSchema {
+                kind: spec.kind(),
+                description: description(spec.kind()),
+                params,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        optimizer::{Control, Convergence, OptimizeReport},
+        wave_source::WaveSource,
+        Complex,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[test]
+    fn greedy_cost_by_name() {
+        match OptimizerSpec::from_json(r#"{ "kind": "greedy", "cost": "l2" }"#).unwrap() {
+            OptimizerSpec::Greedy(p) => {
+                assert_eq!(p.cost, CostKind::L2);
+                assert_eq!(p.phase_division, GreedyParams::default().phase_division);
+            }
+            spec => panic!("unexpected {:?}", spec),
+        }
+        assert!(OptimizerSpec::from_json(r#"{ "kind": "greedy", "cost": "l3" }"#).is_err());
+    }
+
+    #[test]
+    fn schema_lists_cost_of_greedy() {
+        let greedy = algorithms()
+            .into_iter()
+            .find(|a| a.kind == "greedy")
+            .unwrap();
+        let cost = greedy.params.iter().find(|p| p.name == "cost").unwrap();
+        assert_eq!(cost.default, Value::String("complex".to_string()));
+        assert!(cost.type_name.contains("relative"));
+    }
+
+    fn run_pipeline(monitor: Monitor) -> OptimizeReport {
+        let spec = OptimizerSpec::from_json(
+            r#"{ "kind": "pipeline", "stages": [{ "kind": "gspat", "repeat": 3 }, { "kind": "gs", "repeat": 2 }] }"#,
+        )
+        .unwrap();
+        let mut pipeline = spec.build_with_monitor(monitor);
+        pipeline.set_target_foci(&[[0., 0., 100.]]);
+        pipeline.set_target_amps(&[1.]);
+        let mut wave_source: Vec<_> = (0..4)
+            .map(|i| WaveSource::new([i as Float * 10., 0., 0.], Complex::new(0., 0.)))
+            .collect();
+        pipeline.optimize(&mut wave_source).unwrap()
+    }
+
+    #[test]
+    fn pipeline_stages_share_the_monitor() {
+        let iterations = Arc::new(Mutex::new(vec![]));
+        let recorded = iterations.clone();
+        let monitor = Monitor::new().with_observer(move |progress| {
+            recorded.lock().unwrap().push(progress.iteration);
+            Control::Continue
+        });
+        let report = run_pipeline(monitor);
+        assert_eq!(*iterations.lock().unwrap(), vec![1, 2, 3, 1, 2]);
+        assert_eq!(report.iterations, 5);
+
+        // The first stage runs out of the budget, and the second one is skipped
+        let report = run_pipeline(Monitor::new().with_time_budget(Duration::from_secs(0)));
+        assert_eq!(report.convergence, Convergence::TimeBudget);
+        assert_eq!(report.iterations, 1);
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * File: report.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use crate::{
+    math_utils::c_norm,
+    optimizer::{OptimizeError, Targets},
+    transfer_matrix::Propagation,
+    wave_source::WaveSource,
+    Complex, Float,
+};
+
+/// Reason why an optimizer stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Convergence {
+    /// The optimizer has run its whole (fixed) schedule.
+    Completed,
+    /// The iteration count reached its upper limit before converging.
+    MaxIterations,
+    /// The gradient became smaller than the threshold.
+    GradientTolerance,
+    /// The step became smaller than the threshold.
+    StepTolerance,
+    /// The observer of the `Monitor` requested to stop.
+    Stopped,
+    /// The time budget of the `Monitor` ran out.
+    TimeBudget,
+}
+
+#[derive(Debug, Clone)]
+pub struct OptimizeReport {
+    /// Number of iterations used
+    pub iterations: usize,
+    /// Final value of the cost function
+    pub cost: Float,
+    pub convergence: Convergence,
+    /// Wall-clock time taken by the optimization
+    pub elapsed: Duration,
+}
+
+/// Sum of squared errors between the targets and the field produced by `wave_source`.
+/// The error of a focus whose phase is specified is measured on the complex value, and weighted by its weight.
+/// The weighted amplitudes at the null points and the excesses over the upper bounds are also added.
+/// Used as the cost of the optimizers which have no explicit objective function.
+pub(crate) fn amplitude_error(
+    propagation: &Propagation,
+    targets: &Targets,
+    wave_source: &[WaveSource],
+) -> Result<Float, OptimizeError> {
+    let field = propagation.field(wave_source, &targets.control_points())?;
+    Ok(control_point_error(targets, &field))
+}
+
+/// Same as `amplitude_error`, but takes the field at the control points in the order of `Targets::control_points`
+pub(crate) fn control_point_error(targets: &Targets, field: &[Complex]) -> Float {
+    let m = targets.foci().len();
+    let m_null = m + targets.null_points().len();
+    let foci_err: Float = field[0..m]
+        .iter()
+        .zip(targets.amps().iter())
+        .enumerate()
+        .map(|(i, (&p, &amp))| {
+            let e = match targets.complex_amp(i) {
+                Some(t) => (p - t).norm_sqr(),
+                None => {
+                    let e = c_norm(p) - amp;
+                    e * e
+                }
+            };
+            targets.weight(i) * e
+        })
+        .sum();
+    let null_err: Float = field[m..m_null]
+        .iter()
+        .zip(targets.null_weights().iter())
+        .map(|(&p, &w)| w * p.norm_sqr())
+        .sum();
+    let bound_err: Float = field[m_null..]
+        .iter()
+        .zip(targets.bound_limits().iter())
+        .map(|(&p, &limit)| {
+            let e = (c_norm(p) - limit).max(0.);
+            e * e
+        })
+        .sum();
+    foci_err + null_err + bound_err
+}
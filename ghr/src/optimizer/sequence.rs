@@ -0,0 +1,219 @@
+/*
+ * File: sequence.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use crate::{
+    optimizer::{OptimizeError, OptimizeReport, Optimizer, Targets},
+    wave_source::WaveSource,
+    Complex, Float,
+};
+
+/// Optimizer which can keep the emissions close to those of the previous frame
+pub trait Temporal: Optimizer {
+    /// Optimize the emissions while penalizing `weight * |q - previous|^2` for each wave source.
+    fn optimize_regularized(
+        &self,
+        wave_source: &mut [WaveSource],
+        previous: &[Complex],
+        weight: Float,
+    ) -> Result<OptimizeReport, OptimizeError>;
+}
+
+pub(crate) fn check_previous(
+    wave_source: &[WaveSource],
+    previous: &[Complex],
+) -> Result<(), OptimizeError> {
+    if previous.len() != wave_source.len() {
+        return Err(OptimizeError::SourceSizeMismatch {
+            target: "previous emissions",
+            sources: wave_source.len(),
+            len: previous.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Result of a frame in a sequence
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Emissions of the wave sources
+    pub emissions: Vec<Complex>,
+    pub report: OptimizeReport,
+}
+
+/// Sequence of holograms for moving targets
+///
+/// Each frame is optimized with the temporal regularization toward the previous frame,
+/// so that the phases do not jump between the frames.
+pub struct Sequence<O: Temporal> {
+    optimizer: O,
+    smoothness: Float,
+}
+
+impl<O: Temporal> Sequence<O> {
+    /// `smoothness` is the weight of the temporal regularization relative to the errors at the targets.
+    pub fn new(optimizer: O, smoothness: Float) -> Self {
+        Self {
+            optimizer,
+            smoothness,
+        }
+    }
+
+    pub fn optimizer(&self) -> &O {
+        &self.optimizer
+    }
+
+    /// Optimize the frames in order. The first frame is optimized without the regularization.
+    /// `wave_source` holds the emissions of the last frame after the optimization.
+    pub fn optimize(
+        &mut self,
+        frames: &[Targets],
+        wave_source: &mut [WaveSource],
+    ) -> Result<Vec<Frame>, OptimizeError> {
+        let mut result: Vec<Frame> = Vec::with_capacity(frames.len());
+        for targets in frames {
+            *self.optimizer.targets_mut() = targets.clone();
+            let report = match result.last() {
+                Some(previous) => self.optimizer.optimize_regularized(
+                    wave_source,
+                    &previous.emissions,
+                    self.smoothness,
+                )?,
+                None => self.optimizer.optimize(wave_source)?,
+            };
+            result.push(Frame {
+                emissions: wave_source.iter().map(|s| s.q).collect(),
+                report,
+            });
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{Convergence, LM};
+    use std::{cell::RefCell, time::Duration};
+
+    /// Emits the target amplitude of the first focus and records the previous emissions it is given.
+    struct Recorder {
+        targets: Targets,
+        previous: RefCell<Vec<Vec<Complex>>>,
+    }
+
+    impl Optimizer for Recorder {
+        fn targets(&self) -> &Targets {
+            &self.targets
+        }
+
+        fn targets_mut(&mut self) -> &mut Targets {
+            &mut self.targets
+        }
+
+        fn optimize(
+            &self,
+            wave_source: &mut [WaveSource],
+        ) -> Result<OptimizeReport, OptimizeError> {
+            for source in wave_source.iter_mut() {
+                source.q = Complex::new(self.targets.amps()[0], 0.);
+            }
+            Ok(OptimizeReport {
+                iterations: 1,
+                cost: 0.,
+                convergence: Convergence::Completed,
+                elapsed: Duration::default(),
+            })
+        }
+    }
+
+    impl Temporal for Recorder {
+        fn optimize_regularized(
+            &self,
+            wave_source: &mut [WaveSource],
+            previous: &[Complex],
+            _weight: Float,
+        ) -> Result<OptimizeReport, OptimizeError> {
+            self.previous.borrow_mut().push(previous.to_vec());
+            self.optimize(wave_source)
+        }
+    }
+
+    fn frames(amps: &[Float]) -> Vec<Targets> {
+        amps.iter()
+            .map(|&amp| {
+                let mut targets = Targets::new();
+                targets.set_foci(&[[0., 0., 100.]]);
+                targets.set_amps(&[amp]);
+                targets
+            })
+            .collect()
+    }
+
+    fn sources() -> Vec<WaveSource> {
+        (0..16)
+            .map(|i| {
+                let pos = [(i % 4) as Float * 10., (i / 4) as Float * 10., 0.];
+                WaveSource::new(pos, Complex::new(0., 0.))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn previous_frame_is_the_prior_of_the_next() {
+        let recorder = Recorder {
+            targets: Targets::new(),
+            previous: RefCell::new(vec![]),
+        };
+        let mut sequence = Sequence::new(recorder, 1.0);
+        let mut wave_source = sources();
+        let result = sequence
+            .optimize(&frames(&[1., 2., 3.]), &mut wave_source)
+            .unwrap();
+
+        let previous = sequence.optimizer().previous.borrow();
+        assert_eq!(previous.len(), 2);
+        assert_eq!(previous[0], result[0].emissions);
+        assert_eq!(previous[1], result[1].emissions);
+        assert_eq!(previous[1][0], Complex::new(2., 0.));
+    }
+
+    /// Sum of the squared changes of the emissions
+    fn change(a: &[Complex], b: &[Complex]) -> Float {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| (a - b).norm_sqr())
+            .sum()
+    }
+
+    #[test]
+    fn smoothness_keeps_lm_close_to_previous_frame() {
+        let mut wave_source = sources();
+        let mut lm = LM::new(1e-8, 1e-8, 1e-3, 50);
+        *lm.targets_mut() = frames(&[0.02])[0].clone();
+        lm.optimize(&mut wave_source).unwrap();
+        let previous: Vec<_> = wave_source.iter().map(|s| s.q).collect();
+
+        let mut moved = frames(&[0.02])[0].clone();
+        moved.set_foci(&[[20., 20., 100.]]);
+        *lm.targets_mut() = moved;
+        let mut free = wave_source.clone();
+        lm.optimize_regularized(&mut free, &previous, 1e-8).unwrap();
+        let mut smooth = wave_source.clone();
+        lm.optimize_regularized(&mut smooth, &previous, 1e-2)
+            .unwrap();
+
+        let free_change = change(&free.iter().map(|s| s.q).collect::<Vec<_>>(), &previous);
+        let smooth_change = change(&smooth.iter().map(|s| s.q).collect::<Vec<_>>(), &previous);
+        assert!(smooth_change < free_change);
+    }
+}
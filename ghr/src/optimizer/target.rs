@@ -0,0 +1,261 @@
+/*
+ * File: target.rs
+ * Project: optimizer
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use crate::{
+    math_utils::c_norm, optimizer::OptimizeError, wave_source::WaveSource, Complex, Float, Vector3,
+};
+
+/// Maximum number of outer iterations to enforce the upper bounds
+pub(crate) const MAX_ACTIVE_SET_ITER: usize = 10;
+
+/// Target field of the optimization
+///
+/// The phase at each focus is free unless specified by `set_phases`.
+/// Besides the foci, the targets can have null points, where the amplitude should be zero,
+/// and upper-bound points, where the amplitude should not exceed the limit.
+#[derive(Debug, Clone, Default)]
+pub struct Targets {
+    foci: Vec<Vector3>,
+    amps: Vec<Float>,
+    phases: Vec<Option<Float>>,
+    weights: Vec<Float>,
+    null_points: Vec<Vector3>,
+    null_weights: Vec<Float>,
+    bound_points: Vec<Vector3>,
+    bound_limits: Vec<Float>,
+}
+
+impl Targets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn foci(&self) -> &[Vector3] {
+        &self.foci
+    }
+
+    pub fn amps(&self) -> &[Float] {
+        &self.amps
+    }
+
+    pub fn set_foci(&mut self, foci: &[Vector3]) {
+        self.foci = foci.to_vec();
+    }
+
+    pub fn set_amps(&mut self, amps: &[Float]) {
+        self.amps = amps.to_vec();
+    }
+
+    /// Specify the phase at each focus. `None` means the phase is free.
+    pub fn set_phases(&mut self, phases: &[Option<Float>]) {
+        self.phases = phases.to_vec();
+    }
+
+    /// Specify the importance of each focus. All foci have the weight of 1 by default.
+    pub fn set_weights(&mut self, weights: &[Float]) {
+        self.weights = weights.to_vec();
+    }
+
+    /// Weight of `i`-th focus
+    pub fn weight(&self, i: usize) -> Float {
+        self.weights.get(i).copied().unwrap_or(1.0)
+    }
+
+    /// Specify both the amplitude and the phase at each focus.
+    pub fn set_complex_amps(&mut self, values: &[Complex]) {
+        self.amps = values.iter().map(|&v| c_norm(v)).collect();
+        self.phases = values.iter().map(|v| Some(v.arg())).collect();
+    }
+
+    /// Phase of `i`-th focus if specified
+    pub fn phase(&self, i: usize) -> Option<Float> {
+        self.phases.get(i).copied().flatten()
+    }
+
+    pub fn has_fixed_phase(&self) -> bool {
+        self.phases.iter().any(|p| p.is_some())
+    }
+
+    pub fn null_points(&self) -> &[Vector3] {
+        &self.null_points
+    }
+
+    pub fn null_weights(&self) -> &[Float] {
+        &self.null_weights
+    }
+
+    pub fn bound_points(&self) -> &[Vector3] {
+        &self.bound_points
+    }
+
+    pub fn bound_limits(&self) -> &[Float] {
+        &self.bound_limits
+    }
+
+    /// Set the points where the amplitude should be zero.
+    /// The weight is relative to the error at the foci.
+    pub fn set_null_points(&mut self, points: &[Vector3], weights: &[Float]) {
+        self.null_points = points.to_vec();
+        self.null_weights = weights.to_vec();
+    }
+
+    /// Set the points where the amplitude should be less than or equal to the limit.
+    pub fn set_upper_bounds(&mut self, points: &[Vector3], limits: &[Float]) {
+        self.bound_points = points.to_vec();
+        self.bound_limits = limits.to_vec();
+    }
+
+    /// Complex target value of `i`-th focus if its phase is specified
+    pub fn complex_amp(&self, i: usize) -> Option<Complex> {
+        self.phase(i)
+            .map(|phase| self.amps[i] * Complex::new(0., phase).exp())
+    }
+
+    /// The foci, the null points and the upper-bound points in this order
+    pub(crate) fn control_points(&self) -> Vec<Vector3> {
+        self.foci
+            .iter()
+            .chain(self.null_points.iter())
+            .chain(self.bound_points.iter())
+            .copied()
+            .collect()
+    }
+
+    /// Reject the null points and the upper bounds for `optimizer`, which only takes the foci into account.
+    pub(crate) fn check_foci_only(&self, optimizer: &'static str) -> Result<(), OptimizeError> {
+        let target = if !self.null_points.is_empty() {
+            "null points"
+        } else if !self.bound_points.is_empty() {
+            "upper bounds"
+        } else {
+            return Ok(());
+        };
+        Err(OptimizeError::UnsupportedControlPoints { optimizer, target })
+    }
+
+    /// Reject the weights other than 1 and the fixed phases for `optimizer`, which only takes the amplitudes of the foci into account.
+    pub(crate) fn check_amps_only(&self, optimizer: &'static str) -> Result<(), OptimizeError> {
+        self.check_foci_only(optimizer)?;
+        let target = if self.weights.iter().any(|&w| w != 1.0) {
+            "weights of the foci"
+        } else if self.has_fixed_phase() {
+            "fixed phases"
+        } else {
+            return Ok(());
+        };
+        Err(OptimizeError::UnsupportedTarget { optimizer, target })
+    }
+
+    pub(crate) fn check(
+        &self,
+        wave_source: &[WaveSource],
+        min_foci: usize,
+    ) -> Result<(), OptimizeError> {
+        let m = self.foci.len();
+        if self.amps.len() != m {
+            return Err(OptimizeError::TargetSizeMismatch {
+                target: "amplitudes",
+                foci: m,
+                len: self.amps.len(),
+            });
+        }
+        if !self.phases.is_empty() && self.phases.len() != m {
+            return Err(OptimizeError::TargetSizeMismatch {
+                target: "phases",
+                foci: m,
+                len: self.phases.len(),
+            });
+        }
+        if !self.weights.is_empty() && self.weights.len() != m {
+            return Err(OptimizeError::TargetSizeMismatch {
+                target: "weights",
+                foci: m,
+                len: self.weights.len(),
+            });
+        }
+        if self.null_points.len() != self.null_weights.len() {
+            return Err(OptimizeError::ControlPointSizeMismatch {
+                target: "null weights",
+                points: self.null_points.len(),
+                len: self.null_weights.len(),
+            });
+        }
+        if self.bound_points.len() != self.bound_limits.len() {
+            return Err(OptimizeError::ControlPointSizeMismatch {
+                target: "upper bounds",
+                points: self.bound_points.len(),
+                len: self.bound_limits.len(),
+            });
+        }
+        if m < min_foci {
+            return Err(OptimizeError::TooFewFoci {
+                required: min_foci,
+                actual: m,
+            });
+        }
+        if wave_source.is_empty() {
+            return Err(OptimizeError::NoWaveSource);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::optimizer::{Horn, Optimizer, GSPAT};
+
+    #[test]
+    fn foci_only_optimizer_rejects_null_points() {
+        let mut gspat = GSPAT::new(10);
+        gspat.set_target_foci(&[[0., 0., 100.]]);
+        gspat.set_target_amps(&[1.]);
+        gspat.set_null_points(&[[10., 0., 100.]], &[1.]);
+        let mut wave_source = vec![WaveSource::new([0., 0., 0.], Complex::new(0., 0.))];
+        match gspat.optimize(&mut wave_source) {
+            Err(OptimizeError::UnsupportedControlPoints {
+                target: "null points",
+                ..
+            }) => (),
+            r => panic!("unexpected {:?}", r.map(|r| r.cost)),
+        }
+
+        let mut targets = Targets::new();
+        targets.set_upper_bounds(&[[10., 0., 100.]], &[0.1]);
+        assert!(targets.check_foci_only("GSPAT").is_err());
+        assert!(Targets::new().check_foci_only("GSPAT").is_ok());
+    }
+
+    #[test]
+    fn horn_rejects_weights_and_fixed_phases() {
+        let mut horn = Horn::new(10, 1e-3, 0.9);
+        horn.set_target_foci(&[[0., 0., 100.], [10., 0., 100.]]);
+        horn.set_target_amps(&[1., 1.]);
+        horn.set_target_weights(&[1., 2.]);
+        let mut wave_source = vec![WaveSource::new([0., 0., 0.], Complex::new(0., 0.))];
+        match horn.optimize(&mut wave_source) {
+            Err(OptimizeError::UnsupportedTarget {
+                target: "weights of the foci",
+                ..
+            }) => (),
+            r => panic!("unexpected {:?}", r.map(|r| r.cost)),
+        }
+
+        let mut targets = Targets::new();
+        targets.set_phases(&[Some(0.), None]);
+        assert!(targets.check_amps_only("Horn").is_err());
+        targets.set_phases(&[None, None]);
+        targets.set_weights(&[1., 1.]);
+        assert!(targets.check_amps_only("Horn").is_ok());
+    }
+}
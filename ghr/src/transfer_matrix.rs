@@ -0,0 +1,270 @@
+/*
+ * File: transfer_matrix.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: agent
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: agent (agent@local)
+ * -----
+ * Copyright (c) 2026 Hapis Lab. All rights reserved.
+ *
+ */
+
+use std::{
+    collections::VecDeque,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use ndarray::*;
+use rayon::prelude::*;
+
+use crate::{
+    load::{parse_complex_csv, parse_npy, LoadError},
+    optimizer::OptimizeError,
+    utils::transfer,
+    wave_source::WaveSource,
+    Complex, Vector3,
+};
+
+/// Transfer functions from the sources to the points, i.e., `matrix()[[i, j]] = transfer(sources[j], points[i])`
+///
+/// The matrix may be the first rows of a shared one, such as an imported matrix.
+#[derive(Debug, Clone)]
+pub struct TransferMatrix {
+    sources: Vec<Vector3>,
+    points: Vec<Vector3>,
+    matrix: Arc<Array2<Complex>>,
+}
+
+impl TransferMatrix {
+    /// Build the matrix in parallel.
+    pub fn new(sources: &[Vector3], points: &[Vector3]) -> Self {
+        let n = sources.len();
+        let data: Vec<_> = (0..points.len() * n)
+            .into_par_iter()
+            .map(|k| transfer(sources[k % n], points[k / n]))
+            .collect();
+        Self {
+            sources: sources.to_vec(),
+            points: points.to_vec(),
+            matrix: Arc::new(Array::from_shape_vec((points.len(), n), data).unwrap()),
+        }
+    }
+
+    pub fn sources(&self) -> &[Vector3] {
+        &self.sources
+    }
+
+    pub fn points(&self) -> &[Vector3] {
+        &self.points
+    }
+
+    pub fn matrix(&self) -> ArrayView2<'_, Complex> {
+        self.matrix.slice(s![..self.points.len(), ..])
+    }
+
+    fn is_for(&self, sources: &[Vector3], points: &[Vector3]) -> bool {
+        self.sources == sources && self.points == points
+    }
+}
+
+/// Cache of transfer matrices keyed by the positions of the sources and the points
+///
+/// The cache can be shared by the optimizers and the calculator, so that
+/// the repeated optimizations on the same geometry skip rebuilding the matrix.
+/// The least recently used matrix is dropped when the cache is full.
+pub struct TransferCache {
+    capacity: usize,
+    entries: Mutex<VecDeque<Arc<TransferMatrix>>>,
+}
+
+impl TransferCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// The lock is not held while a missing matrix is built, which runs on the rayon thread pool.
+    pub fn get(&self, sources: &[Vector3], points: &[Vector3]) -> Arc<TransferMatrix> {
+        if let Some(entry) = Self::touch(&mut self.entries.lock().unwrap(), sources, points) {
+            return entry;
+        }
+        let entry = Arc::new(TransferMatrix::new(sources, points));
+        if self.capacity == 0 {
+            return entry;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        // Another thread may have inserted the same matrix in the meantime
+        if let Some(entry) = Self::touch(&mut entries, sources, points) {
+            return entry;
+        }
+        if entries.len() == self.capacity {
+            entries.pop_back();
+        }
+        entries.push_front(entry.clone());
+        entry
+    }
+
+    /// Move the matching entry to the front and return it.
+    fn touch(
+        entries: &mut VecDeque<Arc<TransferMatrix>>,
+        sources: &[Vector3],
+        points: &[Vector3],
+    ) -> Option<Arc<TransferMatrix>> {
+        let i = entries.iter().position(|e| e.is_for(sources, points))?;
+        let entry = entries.remove(i).unwrap();
+        entries.push_front(entry.clone());
+        Some(entry)
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Transfer matrix from `wave_source` to `points`, taken from `cache` if given
+pub(crate) fn transfer_matrix(
+    cache: Option<&TransferCache>,
+    wave_source: &[WaveSource],
+    points: &[Vector3],
+) -> Arc<TransferMatrix> {
+    let sources: Vec<_> = wave_source.iter().map(|s| s.pos).collect();
+    match cache {
+        Some(cache) => cache.get(&sources, points),
+        None => Arc::new(TransferMatrix::new(&sources, points)),
+    }
+}
+
+/// Model of the propagation from the sources to the control points used by the optimizers
+///
+/// The optimizers take it by their `with_propagation`, e.g., `Propagation::Imported` to optimize against a measured transfer matrix.
+/// `Propagation::Cached` shares the transfer matrices with the other optimizers and the calculator through a `TransferCache`.
+#[derive(Clone, Default)]
+pub enum Propagation {
+    /// The transfer functions are computed from the positions of the sources and the control points.
+    #[default]
+    Analytic,
+    /// Same as `Analytic`, but the transfer matrices are taken from the cache.
+    Cached(Arc<TransferCache>),
+    /// The given transfer matrix is used regardless of the positions, e.g., a measured one.
+    ///
+    /// The columns are the sources, and the rows are the control points in the order of the foci, the null points and the upper bounds.
+    /// The matrix may have extra rows, which are used by the optimizers requiring only the foci.
+    /// `Optimizer::optimize_masked` uses the columns of the active sources.
+    Imported(Arc<Array2<Complex>>),
+}
+
+impl Propagation {
+    pub fn imported(matrix: Array2<Complex>) -> Self {
+        Propagation::Imported(Arc::new(matrix))
+    }
+
+    /// Load the transfer matrix from a NumPy `.npy` file of a complex 2-dimensional array.
+    pub fn from_npy<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Ok(Self::imported(parse_npy(&fs::read(path)?)?))
+    }
+
+    /// Load the transfer matrix from a CSV file, where each line is a row with the real and imaginary parts of the elements alternately.
+    pub fn from_csv<P: AsRef<Path>>(path: P) -> Result<Self, LoadError> {
+        Ok(Self::imported(parse_complex_csv(&fs::read_to_string(
+            path,
+        )?)?))
+    }
+
+    /// Propagation to the control points from the `columns`-th ones of `sources` sources.
+    /// The columns of an imported matrix are picked if it has those for all the sources, otherwise it is left to fail on the size.
+    pub(crate) fn select_sources(&self, sources: usize, columns: &[usize]) -> Self {
+        match self {
+            Propagation::Imported(matrix)
+                if matrix.ncols() == sources && columns.len() < sources =>
+            {
+                Self::imported(matrix.select(Axis(1), columns))
+            }
+            propagation => propagation.clone(),
+        }
+    }
+
+    /// Transfer matrix from `wave_source` to `points`, which must be the first control points for `Imported`.
+    pub(crate) fn matrix(
+        &self,
+        wave_source: &[WaveSource],
+        points: &[Vector3],
+    ) -> Result<Arc<TransferMatrix>, OptimizeError> {
+        match self {
+            Propagation::Analytic => Ok(transfer_matrix(None, wave_source, points)),
+            Propagation::Cached(cache) => Ok(transfer_matrix(Some(cache), wave_source, points)),
+            Propagation::Imported(matrix) => {
+                let (rows, cols) = matrix.dim();
+                if rows < points.len() || cols != wave_source.len() {
+                    return Err(OptimizeError::TransferMatrixSizeMismatch {
+                        rows,
+                        cols,
+                        points: points.len(),
+                        sources: wave_source.len(),
+                    });
+                }
+                Ok(Arc::new(TransferMatrix {
+                    sources: wave_source.iter().map(|s| s.pos).collect(),
+                    points: points.to_vec(),
+                    matrix: matrix.clone(),
+                }))
+            }
+        }
+    }
+
+    /// Field produced by `wave_source` at `points`, which must be the first control points for `Imported`.
+    pub(crate) fn field(
+        &self,
+        wave_source: &[WaveSource],
+        points: &[Vector3],
+    ) -> Result<Vec<Complex>, OptimizeError> {
+        match self {
+            Propagation::Imported(_) => {
+                let q: Array1<Complex> = wave_source.iter().map(|s| s.q).collect();
+                Ok(self.matrix(wave_source, points)?.matrix().dot(&q).to_vec())
+            }
+            _ => Ok(points
+                .iter()
+                .map(|&p| {
+                    wave_source.iter().fold(Complex::new(0., 0.), |acc, s| {
+                        acc + s.q * transfer(s.pos, p)
+                    })
+                })
+                .collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Float;
+
+    #[test]
+    fn cache_shares_and_evicts() {
+        let sources = [[0., 0., 0.], [10., 0., 0.]];
+        let a = [[0., 0., 100.]];
+        let b = [[0., 10., 100.]];
+        let cache = TransferCache::new(1);
+        let m = cache.get(&sources, &a);
+        assert!(Arc::ptr_eq(&m, &cache.get(&sources, &a)));
+        assert_eq!(m.matrix()[[0, 1]], transfer(sources[1], a[0]));
+        cache.get(&sources, &b);
+        assert!(!Arc::ptr_eq(&m, &cache.get(&sources, &a)));
+    }
+
+    #[test]
+    fn cache_is_reentrant_from_rayon() {
+        let sources: Vec<_> = (0..16).map(|i| [i as Float, 0., 0.]).collect();
+        let cache = TransferCache::new(4);
+        (0..32).into_par_iter().for_each(|i| {
+            let points = [[0., (i % 4) as Float, 100.]];
+            assert_eq!(cache.get(&sources, &points).points(), &points);
+        });
+    }
+}
@@ -18,7 +18,7 @@ import re
 import glob
 import os
 
-methods = {'horn': 'SDP+BCD', 'long': 'EVD', 'lm': 'LM', 'gspat': 'GS-PAT',
+methods = {'naive': 'Naive', 'gs': 'GS', 'horn': 'SDP+BCD', 'long': 'EVD', 'lm': 'LM', 'gspat': 'GS-PAT',
            'gbf_256_16': r'Proposed $(K=16, L=256)$', 'gbf_16_1': r'Proposed $(K=1, L=16)$'}
 
 
@@ -13,9 +13,10 @@ Copyright (c) 2020 Hapis Lab. All rights reserved.
 
 
 from enum import IntEnum
+import json
 
 import ctypes
-from ctypes import c_void_p, byref, c_ulong, c_int, POINTER, c_double, c_bool
+from ctypes import c_void_p, byref, c_ulong, c_int, POINTER, c_double, c_bool, create_string_buffer
 import numpy as np
 
 from . import nativemethods
@@ -52,6 +53,23 @@ class Calculator:
         array = ctypes.cast(ptr, POINTER(WaveSource * size)).contents
         return array
 
+    def set_calibration(self, phase_offsets, gains):
+        """Set the phase offset in radians and the gain of each wave source. Empty lists remove the calibration.
+
+        The calculation uses the drives modified by the calibration, and Optimizer.optimize compensates it.
+        ValueError is raised if the lengths of phase_offsets and gains are different.
+        """
+        size = len(gains)
+        if len(phase_offsets) != size:
+            raise ValueError('phase_offsets and gains must have the same length')
+        phase_offsets = np.ctypeslib.as_ctypes(np.array(phase_offsets).astype(np.float64)) if size > 0 else None
+        gains = np.ctypeslib.as_ctypes(np.array(gains).astype(np.float64)) if size > 0 else None
+        nativemethods.GHR_DLL.GHR_SetCalibration(self.handle, phase_offsets, c_ulong(size), gains, c_ulong(size))
+
+    def load_calibration(self, path: str):
+        """Load the calibration from a CSV file, where each line has the phase offset and the gain of a source."""
+        return nativemethods.GHR_DLL.GHR_LoadCalibration(self.handle, path.encode('utf-8'))
+
 
 class ScalarBuffer:
     def __init__(self):
@@ -168,17 +186,18 @@ class CpuCalculator(Calculator):
 class Optimizer():
     @staticmethod
     def greedy_brute_force(calculate: Calculator, foci, amps, phase_div: int = 16,
-                           amp_div: int = 16, randomize: bool = False):
+                           amp_div: int = 16, randomize: bool = False, weights=None):
         size = len(foci)
         amps = np.array(amps).astype(np.float64)
         amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
         foci_array = np.zeros([size * 3]).astype(np.float64)
         for i, focus in enumerate(foci):
             foci_array[3 * i] = focus[0]
             foci_array[3 * i + 1] = focus[1]
             foci_array[3 * i + 2] = focus[2]
         foci_array = np.ctypeslib.as_ctypes(foci_array)
-        nativemethods.GHR_DLL.GHR_GreedyBruteForce(calculate.handle, foci_array, amps, c_ulong(
+        return nativemethods.GHR_DLL.GHR_GreedyBruteForceWeighted(calculate.handle, foci_array, amps, weights, c_ulong(
             size), c_ulong(phase_div), c_ulong(amp_div), c_bool(randomize))
 
     @staticmethod
@@ -192,7 +211,7 @@ class Optimizer():
             foci_array[3 * i + 1] = focus[1]
             foci_array[3 * i + 2] = focus[2]
         foci_array = np.ctypeslib.as_ctypes(foci_array)
-        nativemethods.GHR_DLL.GHR_Horn(
+        return nativemethods.GHR_DLL.GHR_Horn(
             calculate.handle,
             foci_array,
             amps,
@@ -202,34 +221,37 @@ class Optimizer():
             c_double(plambda))
 
     @staticmethod
-    def long2014(calculate: Calculator, foci, amps, gamma: float = 1.0):
+    def long2014(calculate: Calculator, foci, amps, gamma: float = 1.0, weights=None):
         size = len(foci)
         amps = np.array(amps).astype(np.float64)
         amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
         foci_array = np.zeros([size * 3]).astype(np.float64)
         for i, focus in enumerate(foci):
             foci_array[3 * i] = focus[0]
             foci_array[3 * i + 1] = focus[1]
             foci_array[3 * i + 2] = focus[2]
         foci_array = np.ctypeslib.as_ctypes(foci_array)
-        nativemethods.GHR_DLL.GHR_Long(calculate.handle, foci_array, amps, c_ulong(size), c_double(gamma))
+        return nativemethods.GHR_DLL.GHR_LongWeighted(calculate.handle, foci_array, amps, weights, c_ulong(size), c_double(gamma))
 
     @staticmethod
     def levenberg_marquardt(calculate: Calculator, foci, amps, eps_1: float = 1e-8,
-                            eps_2: float = 1e-8, tau: float = 1e-3, repeat: int = 200):
+                            eps_2: float = 1e-8, tau: float = 1e-3, repeat: int = 200, weights=None):
         size = len(foci)
         amps = np.array(amps).astype(np.float64)
         amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
         foci_array = np.zeros([size * 3]).astype(np.float64)
         for i, focus in enumerate(foci):
             foci_array[3 * i] = focus[0]
             foci_array[3 * i + 1] = focus[1]
             foci_array[3 * i + 2] = focus[2]
         foci_array = np.ctypeslib.as_ctypes(foci_array)
-        nativemethods.GHR_DLL.GHR_LM(
+        return nativemethods.GHR_DLL.GHR_LMWeighted(
             calculate.handle,
             foci_array,
             amps,
+            weights,
             c_ulong(size),
             c_double(eps_1),
             c_double(eps_2),
@@ -237,14 +259,113 @@ class Optimizer():
             c_ulong(repeat))
 
     @staticmethod
-    def gspat(calculate: Calculator, foci, amps, repeat: int = 100):
+    def gspat(calculate: Calculator, foci, amps, repeat: int = 100, weights=None):
+        size = len(foci)
+        amps = np.array(amps).astype(np.float64)
+        amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
+        foci_array = np.zeros([size * 3]).astype(np.float64)
+        for i, focus in enumerate(foci):
+            foci_array[3 * i] = focus[0]
+            foci_array[3 * i + 1] = focus[1]
+            foci_array[3 * i + 2] = focus[2]
+        foci_array = np.ctypeslib.as_ctypes(foci_array)
+        return nativemethods.GHR_DLL.GHR_GSPATWeighted(calculate.handle, foci_array, amps, weights, c_ulong(size), c_ulong(repeat))
+
+    @staticmethod
+    def naive(calculate: Calculator, foci, amps, weights=None):
+        size = len(foci)
+        amps = np.array(amps).astype(np.float64)
+        amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
+        foci_array = np.zeros([size * 3]).astype(np.float64)
+        for i, focus in enumerate(foci):
+            foci_array[3 * i] = focus[0]
+            foci_array[3 * i + 1] = focus[1]
+            foci_array[3 * i + 2] = focus[2]
+        foci_array = np.ctypeslib.as_ctypes(foci_array)
+        return nativemethods.GHR_DLL.GHR_NaiveWeighted(calculate.handle, foci_array, amps, weights, c_ulong(size))
+
+    @staticmethod
+    def gs(calculate: Calculator, foci, amps, repeat: int = 100, weights=None):
         size = len(foci)
         amps = np.array(amps).astype(np.float64)
         amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
         foci_array = np.zeros([size * 3]).astype(np.float64)
         for i, focus in enumerate(foci):
             foci_array[3 * i] = focus[0]
             foci_array[3 * i + 1] = focus[1]
             foci_array[3 * i + 2] = focus[2]
         foci_array = np.ctypeslib.as_ctypes(foci_array)
-        nativemethods.GHR_DLL.GHR_GSPAT(calculate.handle, foci_array, amps, c_ulong(size), c_ulong(repeat))
+        return nativemethods.GHR_DLL.GHR_GSWeighted(calculate.handle, foci_array, amps, weights, c_ulong(size), c_ulong(repeat))
+
+    @staticmethod
+    def optimize(calculate: Calculator, foci, amps, spec, weights=None, callback=None, time_budget: float = 0.0, mask=None,
+                 transfer_matrix=None):
+        """spec is a dict such as {'kind': 'gspat', 'repeat': 100}. Omitted parameters take their default values.
+
+        callback is called as callback(iteration, cost, elapsed) at each iteration, and the optimization stops if it returns False.
+        The optimization also stops after time_budget seconds if it is positive.
+
+        mask is a list of booleans over the wave sources. Only the sources with True are optimized, and the others are turned off.
+        ValueError is raised if its length is different from the number of the wave sources.
+
+        transfer_matrix is a complex matrix used instead of the transfer functions computed from the positions, e.g., np.load('G.npy').
+        Its rows are the control points, i.e., the foci, and its columns are the wave sources.
+
+        Returns False if spec is invalid or the optimization fails. The reason is given by last_error().
+        """
+        size = len(foci)
+        amps = np.array(amps).astype(np.float64)
+        amps = np.ctypeslib.as_ctypes(amps)
+        weights = None if weights is None else np.ctypeslib.as_ctypes(np.array(weights).astype(np.float64))
+        foci_array = np.zeros([size * 3]).astype(np.float64)
+        for i, focus in enumerate(foci):
+            foci_array[3 * i] = focus[0]
+            foci_array[3 * i + 1] = focus[1]
+            foci_array[3 * i + 2] = focus[2]
+        foci_array = np.ctypeslib.as_ctypes(foci_array)
+        spec = json.dumps(spec).encode('utf-8')
+        if mask is not None:
+            mask = np.array(mask).astype(np.bool_)
+            if len(mask) != len(calculate.wave_sources()):
+                raise ValueError('mask must have the same length as the wave sources')
+            mask_len = len(mask)
+            mask = np.ctypeslib.as_ctypes(mask)
+        else:
+            mask_len = 0
+        if callback is None and time_budget <= 0.0 and mask is None and transfer_matrix is None:
+            return nativemethods.GHR_DLL.GHR_Optimize(calculate.handle, spec, foci_array, amps, weights, c_ulong(size))
+        if callback is None:
+            observer = nativemethods.PROGRESS_CALLBACK()
+        else:
+            observer = nativemethods.PROGRESS_CALLBACK(lambda i, cost, elapsed, _: callback(i, cost, elapsed) is not False)
+        if transfer_matrix is not None:
+            matrix = np.ascontiguousarray(transfer_matrix, dtype=np.complex128)
+            rows = matrix.shape[0]
+            matrix = np.ctypeslib.as_ctypes(matrix.view(np.float64).ravel())
+            return nativemethods.GHR_DLL.GHR_OptimizeWithTransferMatrix(calculate.handle, spec, foci_array, amps, weights, c_ulong(size),
+                                                                       mask, c_ulong(mask_len), observer, None, c_double(time_budget),
+                                                                       matrix, c_ulong(rows))
+        if mask is None:
+            return nativemethods.GHR_DLL.GHR_OptimizeWithCallback(calculate.handle, spec, foci_array, amps, weights, c_ulong(size),
+                                                                 observer, None, c_double(time_budget))
+        return nativemethods.GHR_DLL.GHR_OptimizeMasked(calculate.handle, spec, foci_array, amps, weights, c_ulong(size),
+                                                       mask, c_ulong(mask_len), observer, None, c_double(time_budget))
+
+    @staticmethod
+    def algorithms():
+        """List of the available algorithms with their parameters and default values"""
+        size = nativemethods.GHR_DLL.GHR_Algorithms(None, c_ulong(0))
+        buf = create_string_buffer(size)
+        nativemethods.GHR_DLL.GHR_Algorithms(buf, c_ulong(size))
+        return json.loads(buf.value.decode('utf-8'))
+
+    @staticmethod
+    def last_error():
+        """Reason why the last optimize call failed, or an empty string if it succeeded"""
+        size = nativemethods.GHR_DLL.GHR_LastError(None, c_ulong(0))
+        buf = create_string_buffer(size)
+        nativemethods.GHR_DLL.GHR_LastError(buf, c_ulong(size))
+        return buf.value.decode('utf-8')
@@ -13,7 +13,10 @@ Copyright (c) 2020 Hapis Lab. All rights reserved.
 
 
 import ctypes
-from ctypes import c_void_p, c_int, POINTER, c_ulong, Structure, c_double, c_bool
+from ctypes import c_void_p, c_int, POINTER, c_ulong, Structure, c_double, c_bool, c_char_p, CFUNCTYPE
+
+# iteration, cost, elapsed time in seconds, user data -> continue or not
+PROGRESS_CALLBACK = CFUNCTYPE(c_bool, c_ulong, c_double, c_double, c_void_p)
 
 
 class Vector3(Structure):
@@ -74,73 +77,125 @@ def init_dll(dll_location):
 
 def __init_calculator():
     GHR_DLL.GHR_CreateCpuCalculator.argtypes = [POINTER(c_void_p)]
-    GHR_DLL.GHR_CreateCpuCalculator.restypes = [None]
+    GHR_DLL.GHR_CreateCpuCalculator.restype = None
 
     GHR_DLL.GHR_FreeCalculator.argtypes = [c_void_p]
-    GHR_DLL.GHR_FreeCalculator.restypes = [None]
+    GHR_DLL.GHR_FreeCalculator.restype = None
 
     GHR_DLL.GHR_AddWaveSource.argtypes = [c_void_p, WaveSource]
-    GHR_DLL.GHR_AddWaveSource.restypes = [None]
+    GHR_DLL.GHR_AddWaveSource.restype = None
 
     GHR_DLL.GHR_InitWaveSources.argtypes = [c_void_p, c_ulong]
-    GHR_DLL.GHR_InitWaveSources.restypes = [None]
+    GHR_DLL.GHR_InitWaveSources.restype = None
 
     GHR_DLL.GHR_WaveSources.argtypes = [c_void_p, POINTER(c_void_p)]
-    GHR_DLL.GHR_WaveSources.restypes = [c_ulong]
+    GHR_DLL.GHR_WaveSources.restype = c_ulong
+
+    GHR_DLL.GHR_SetCalibration.argtypes = [c_void_p, POINTER(c_double), c_ulong, POINTER(c_double), c_ulong]
+    GHR_DLL.GHR_SetCalibration.restype = c_bool
+
+    GHR_DLL.GHR_LoadCalibration.argtypes = [c_void_p, c_char_p]
+    GHR_DLL.GHR_LoadCalibration.restype = c_bool
 
 
 def __init_builder():
     GHR_DLL.GHR_CreateBufferBuilder.argtypes = [POINTER(c_void_p)]
-    GHR_DLL.GHR_CreateBufferBuilder.restypes = []
+    GHR_DLL.GHR_CreateBufferBuilder.restype = None
 
     GHR_DLL.GHR_FreeBufferBuilder.argtypes = [c_void_p]
-    GHR_DLL.GHR_FreeBufferBuilder.restypes = [None]
+    GHR_DLL.GHR_FreeBufferBuilder.restype = None
 
     GHR_DLL.GHR_BufferBuilder_At.argtypes = [POINTER(c_void_p), c_int, c_double]
-    GHR_DLL.GHR_BufferBuilder_At.restypes = [None]
+    GHR_DLL.GHR_BufferBuilder_At.restype = None
 
     GHR_DLL.GHR_BufferBuilder_Range.argtypes = [POINTER(c_void_p), c_int, c_double, c_double]
-    GHR_DLL.GHR_BufferBuilder_Range.restypes = [None]
+    GHR_DLL.GHR_BufferBuilder_Range.restype = None
 
     GHR_DLL.GHR_BufferBuilder_Resolution.argtypes = [POINTER(c_void_p), c_double]
-    GHR_DLL.GHR_BufferBuilder_Resolution.restypes = [None]
+    GHR_DLL.GHR_BufferBuilder_Resolution.restype = None
 
     GHR_DLL.GHR_BufferBuilder_Generate.argtypes = [c_void_p, c_int, POINTER(c_void_p)]
-    GHR_DLL.GHR_BufferBuilder_Generate.restypes = [None]
+    GHR_DLL.GHR_BufferBuilder_Generate.restype = None
 
 
 def __init_buffer():
     GHR_DLL.GHR_FreeBuffer.argtypes = [c_void_p]
-    GHR_DLL.GHR_FreeBuffer.restypes = [None]
+    GHR_DLL.GHR_FreeBuffer.restype = None
 
     GHR_DLL.GHR_GetScalarBufferArray.argtypes = [c_void_p, POINTER(c_void_p), c_int]
-    GHR_DLL.GHR_GetScalarBufferArray.restypes = [c_ulong]
+    GHR_DLL.GHR_GetScalarBufferArray.restype = c_ulong
 
     GHR_DLL.GHR_GetScalarMax.argtypes = [c_void_p, c_int]
-    GHR_DLL.GHR_GetScalarMax.restypes = [c_double]
+    GHR_DLL.GHR_GetScalarMax.restype = c_double
 
     GHR_DLL.GHR_GetBounds.argtypes = [c_void_p, c_int, POINTER(c_ulong), POINTER(c_ulong), POINTER(c_ulong)]
-    GHR_DLL.GHR_GetBounds.restypes = [None]
+    GHR_DLL.GHR_GetBounds.restype = None
 
     GHR_DLL.GHR_GetDimension.argtypes = [c_void_p, c_int, POINTER(c_int), POINTER(c_int), POINTER(c_int)]
-    GHR_DLL.GHR_GetDimension.restypes = [None]
+    GHR_DLL.GHR_GetDimension.restype = None
 
     GHR_DLL.GHR_Calculate.argtypes = [c_void_p, c_void_p, c_int]
-    GHR_DLL.GHR_Calculate.restypes = [None]
+    GHR_DLL.GHR_Calculate.restype = None
 
 
 def __init_optimizer():
     GHR_DLL.GHR_GreedyBruteForce.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong, c_ulong, c_ulong, c_bool]
-    GHR_DLL.GHR_GreedyBruteForce.restypes = [None]
+    GHR_DLL.GHR_GreedyBruteForce.restype = c_bool
+
+    GHR_DLL.GHR_GreedyBruteForceWeighted.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong, c_ulong, c_ulong, c_bool]
+    GHR_DLL.GHR_GreedyBruteForceWeighted.restype = c_bool
 
     GHR_DLL.GHR_Horn.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong, c_ulong, c_double, c_double]
-    GHR_DLL.GHR_Horn.restypes = [None]
+    GHR_DLL.GHR_Horn.restype = c_bool
 
     GHR_DLL.GHR_Long.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong, c_double]
-    GHR_DLL.GHR_Long.restypes = [None]
+    GHR_DLL.GHR_Long.restype = c_bool
+
+    GHR_DLL.GHR_LongWeighted.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong, c_double]
+    GHR_DLL.GHR_LongWeighted.restype = c_bool
 
     GHR_DLL.GHR_LM.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong, c_double, c_double, c_double, c_ulong]
-    GHR_DLL.GHR_LM.restypes = [None]
+    GHR_DLL.GHR_LM.restype = c_bool
+
+    GHR_DLL.GHR_LMWeighted.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong, c_double, c_double, c_double, c_ulong]
+    GHR_DLL.GHR_LMWeighted.restype = c_bool
 
     GHR_DLL.GHR_GSPAT.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong, c_ulong]
-    GHR_DLL.GHR_GSPAT.restypes = [None]
+    GHR_DLL.GHR_GSPAT.restype = c_bool
+
+    GHR_DLL.GHR_GSPATWeighted.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong, c_ulong]
+    GHR_DLL.GHR_GSPATWeighted.restype = c_bool
+
+    GHR_DLL.GHR_Naive.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong]
+    GHR_DLL.GHR_Naive.restype = c_bool
+
+    GHR_DLL.GHR_NaiveWeighted.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong]
+    GHR_DLL.GHR_NaiveWeighted.restype = c_bool
+
+    GHR_DLL.GHR_GS.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), c_ulong, c_ulong]
+    GHR_DLL.GHR_GS.restype = c_bool
+
+    GHR_DLL.GHR_GSWeighted.argtypes = [c_void_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong, c_ulong]
+    GHR_DLL.GHR_GSWeighted.restype = c_bool
+
+    GHR_DLL.GHR_Optimize.argtypes = [c_void_p, c_char_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong]
+    GHR_DLL.GHR_Optimize.restype = c_bool
+
+    GHR_DLL.GHR_OptimizeWithCallback.argtypes = [c_void_p, c_char_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong,
+                                                 PROGRESS_CALLBACK, c_void_p, c_double]
+    GHR_DLL.GHR_OptimizeWithCallback.restype = c_bool
+
+    GHR_DLL.GHR_OptimizeMasked.argtypes = [c_void_p, c_char_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong,
+                                           POINTER(c_bool), c_ulong, PROGRESS_CALLBACK, c_void_p, c_double]
+    GHR_DLL.GHR_OptimizeMasked.restype = c_bool
+
+    GHR_DLL.GHR_OptimizeWithTransferMatrix.argtypes = [c_void_p, c_char_p, POINTER(c_double), POINTER(c_double), POINTER(c_double), c_ulong,
+                                                       POINTER(c_bool), c_ulong, PROGRESS_CALLBACK, c_void_p, c_double, POINTER(c_double),
+                                                       c_ulong]
+    GHR_DLL.GHR_OptimizeWithTransferMatrix.restype = c_bool
+
+    GHR_DLL.GHR_Algorithms.argtypes = [c_char_p, c_ulong]
+    GHR_DLL.GHR_Algorithms.restype = c_ulong
+
+    GHR_DLL.GHR_LastError.argtypes = [c_char_p, c_ulong]
+    GHR_DLL.GHR_LastError.restype = c_ulong